@@ -0,0 +1,76 @@
+// Times the interpreter against the decode cache on a few long running
+// programs:
+//
+//     cargo bench --bench intcode
+use aoc2019::intcode::IntCodePC;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+const N: i64 = 1_000_000;
+
+// sums N down to 1 in [21]
+//
+//      0: add #0, #N, [20]
+//      4: add [20], [21], [21]
+//      8: add [20], #-1, [20]
+//     12: jnz [20], #4
+fn sum() -> Vec<i64> {
+    vec![1101,0,N,20,1,20,21,21,1001,20,-1,20,1005,20,4,99,0,0,0,0,0,0]
+}
+
+// the same loop on the stack, through relative operands
+//
+//      0: arb #100
+//      2: add #0, #N, rb+0
+//      6: add rb+0, rb+1, rb+1
+//     10: add rb+0, #-1, rb+0
+//     14: jnz rb+0, #6
+fn relative() -> Vec<i64> {
+    vec![109,100,21101,0,N,0,22201,0,1,1,21201,0,-1,0,1205,0,6,99]
+}
+
+// rewrites the first instruction of its loop on every pass
+//
+//      0: add [30], [31], [31]
+//      4: add #0, #1, [0]
+//      8: add [32], #-1, [32]
+//     12: jnz [32], #0
+fn self_modifying() -> Vec<i64> {
+    let mut prog = vec![1,30,31,31,1101,0,1,0,1001,32,-1,32,1005,32,0,99];
+    prog.resize(30, 0);
+    prog.extend(vec![1, 0, N]);
+    prog
+}
+
+// fastest of RUNS runs, with what the program left in memory
+fn time(prog: &[i64], cached: bool) -> (Duration, Vec<i64>) {
+    let mut best = Duration::MAX;
+    let mut memory = Vec::new();
+    for _ in 0 .. RUNS {
+        let mut pc = IntCodePC::new_detached(prog.to_vec());
+        if cached {
+            pc.enable_cache();
+        }
+        let start = Instant::now();
+        pc.run().unwrap();
+        best = best.min(start.elapsed());
+        memory = pc.memory().to_vec();
+    }
+    (best, memory)
+}
+
+fn main() {
+    let benches = [
+        ("sum", sum()),
+        ("relative", relative()),
+        ("self_modifying", self_modifying())
+    ];
+
+    println!("{:<16} {:>12} {:>12} {:>8}", "program", "interpreter", "cached", "speedup");
+    for (name, prog) in benches.iter() {
+        let (plain, expected) = time(prog, false);
+        let (cached, memory) = time(prog, true);
+        assert_eq!(memory, expected, "{}: engines disagree", name);
+        println!("{:<16} {:>12.2?} {:>12.2?} {:>7.2}x", name, plain, cached, plain.as_secs_f64() / cached.as_secs_f64());
+    }
+}
@@ -1,50 +1,326 @@
 extern crate aoc2019;
 
-use aoc2019::intcode::IntCodePC;
+use aoc2019::intcode::{AsciiInput, AsciiOutput, InputPort, IntCodePC, IntcodeError, OutputPort, Snapshot, State, TextOutput, TraceSink, Word};
+use aoc2019::intcode::disasm::disassemble;
+use aoc2019::intcode::asm::{assemble, to_text};
+use aoc2019::intcode::compile::to_rust;
+use aoc2019::intcode::analyze::analyze;
+use aoc2019::intcode::fuzz::{Fuzzer, Options};
+use aoc2019::intcode::debugger::Debugger;
+use aoc2019::intcode::trace::JsonTrace;
+use aoc2019::intcode::session::{Recorder, Replayer, Session};
 use std::env::args;
 use std::process::exit;
 use std::fs::File;
 use std::io::*;
 
+#[cfg(not(feature = "bigint"))]
+type Cell = i64;
+
+#[cfg(feature = "bigint")]
+type Cell = num_bigint::BigInt;
 
 
 fn usage() {
     println!("Usage: intcodepc.exe C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --ascii C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --debug C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --trace C:\\Path\\to\\trace.jsonl C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --record C:\\Path\\to\\session.log C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --replay C:\\Path\\to\\session.log C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --resume C:\\Path\\to\\state.bin");
+    println!("       intcodepc.exe --profile C:\\Path\\to\\report.txt C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe --folded C:\\Path\\to\\stacks.folded C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe disasm C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe asm C:\\Path\\to\\source.s");
+    println!("       intcodepc.exe compile C:\\Path\\to\\program.txt [name]");
+    println!("       intcodepc.exe analyze [--dot] C:\\Path\\to\\program.txt");
+    println!("       intcodepc.exe fuzz [--runs N] [--seed N] [--steps N] C:\\Path\\to\\program.txt C:\\Path\\to\\corpus");
+    println!();
+    println!("--ascii can also go before --trace, --record, --replay, --resume, --profile and --folded");
+}
+
+// in ASCII mode the machine is detached and run() below drives its I/O
+fn machine<'a>(prog: Vec<Cell>, sin: &'a Stdin, sout: &'a mut Stdout, ascii: bool) -> IntCodePC<'a, 'a, Cell> {
+    if ascii {
+        IntCodePC::new_detached(prog)
+    } else {
+        IntCodePC::new_with_stdin(prog, sin, sout)
+    }
+}
+
+// in ASCII mode typed lines go in as character codes and character output
+// is printed as text; the output is buffered and flushed whenever the
+// machine waits for input
+fn run(pc: &mut IntCodePC<'_, '_, Cell>, ascii: bool) -> std::result::Result<Cell, IntcodeError<Cell>> {
+    if !ascii {
+        return pc.run();
+    }
+
+    let mut i = AsciiInput::new(stdin().lock());
+    let mut o = AsciiOutput::new(BufWriter::new(stdout().lock()));
+    let io_error = |pc: &IntCodePC<'_, '_, Cell>, error| IntcodeError::Io {pc: pc.pc(), ins: pc.memory().get(pc.pc()), error};
+    let res = loop {
+        match pc.resume() {
+            Ok(State::NeedsInput) => {
+                o.flush().unwrap();
+                match i.read_word() {
+                    Ok(Some(val)) => pc.feed(val),
+                    // fails the input instruction as an exhausted input does
+                    Ok(None) => break pc.run(),
+                    Err(error) => break Err(io_error(pc, error))
+                }
+            },
+            Ok(State::Output(val)) => {
+                if let Err(error) = o.write_word(val) {
+                    break Err(io_error(pc, error));
+                }
+            },
+            Ok(State::Halted(val)) => break Ok(val),
+            Err(e) => break Err(e)
+        }
+    };
+    o.into_inner().flush().unwrap();
+    res
 }
 
-fn execute(prog: Vec<i32>) -> i32 {
-    
-    
+fn execute(prog: Vec<Cell>, snap: Option<&Snapshot<Cell>>, sink: Option<&mut dyn TraceSink<Cell>>, ascii: bool) -> std::result::Result<Cell, IntcodeError<Cell>> {
     let sin = stdin();
     let mut sout = stdout();
-
-    let mut pc = IntCodePC::new_with_stdin(prog, &sin, &mut sout);
-    pc.run()
+    let mut pc = machine(prog, &sin, &mut sout, ascii);
+    if let Some(snap) = snap {
+        pc.restore(snap);
+    }
+    if let Some(sink) = sink {
+        pc.trace_to(sink);
+    }
+    run(&mut pc, ascii)
 }
 
-fn read_prog(path: &str) -> Vec<i32> {
+fn finish(res: std::result::Result<Cell, IntcodeError<Cell>>) -> ! {
+    match res {
+        Ok(status) => exit(status.to_i64().unwrap_or(-1) as i32),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(-1);
+        }
+    }
+}
 
+fn read_text(path: &str) -> String {
     let mut file = File::open(path).unwrap();
     let mut buf = String::new();
     file.read_to_string(&mut buf).unwrap();
+    buf
+}
+
+fn read_prog<W: Word>(path: &str) -> Vec<W> {
+
+    let buf = read_text(path);
 
     let mut prog = Vec::new();
     for i in buf.split(',').map(|s| s.trim()) {
-        prog.push(i.parse().unwrap())
+        prog.push(W::parse(i).unwrap())
     }
 
     prog
 }
 
+fn disasm(path: &str) {
+    let prog: Vec<Cell> = read_prog(path);
+    print!("{}", disassemble(&prog));
+}
+
+fn asm(path: &str) {
+    match assemble(&read_text(path)) {
+        Ok(prog) => println!("{}", to_text(&prog)),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            exit(-1);
+        }
+    }
+}
+
+// prints the program as a Rust function taking the machine to run it on;
+// the translation is for 64 bit words whatever this was built with
+fn compile(path: &str, name: &str) {
+    let prog: Vec<i64> = read_prog(path);
+    print!("{}", to_rust(&prog, name, &[]));
+}
+
+fn analyze_prog(path: &str, dot: bool) {
+    let prog: Vec<Cell> = read_prog(path);
+    let analysis = analyze(&prog);
+    if dot {
+        print!("{}", analysis.to_dot());
+    } else {
+        print!("{}", analysis.report());
+    }
+}
+
+// fuzzes the program's input and writes the smallest input found for each
+// fault to `dir`; the seed defaults to the clock and is printed for reruns
+fn fuzz(path: &str, dir: &str, opts: &[&str]) {
+    let prog: Vec<i64> = read_prog(path);
+    let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(1);
+    let mut options = Options {seed, ..Default::default()};
+    let mut runs = 100_000;
+    for opt in opts.chunks(2) {
+        let ok = match opt {
+            ["--runs", n] => n.parse().map(|n| runs = n).is_ok(),
+            ["--seed", n] => n.parse().map(|n| options.seed = n).is_ok(),
+            ["--steps", n] => n.parse().map(|n| options.steps = n).is_ok(),
+            _ => false
+        };
+        if !ok {
+            usage();
+            exit(-1);
+        }
+    }
+
+    eprintln!("seed {}", options.seed);
+    let mut fuzzer = Fuzzer::new(prog, options);
+    fuzzer.run(runs);
+    eprintln!("{} runs, {} addresses covered, {} inputs kept", fuzzer.execs(), fuzzer.coverage(), fuzzer.queue().len());
+
+    let paths = fuzzer.write_crashes(std::path::Path::new(dir)).unwrap();
+    for ((fault, input), path) in fuzzer.crashes().iter().zip(paths) {
+        let input: Vec<String> = input.iter().map(|v| v.to_string()).collect();
+        println!("{}: [{}] in {}", fault, input.join(", "), path);
+    }
+}
+
+fn debug(path: &str) {
+    let prog: Vec<Cell> = read_prog(path);
+    let mut dbg = Debugger::new(prog);
+    let sin = stdin();
+    let mut sout = stdout();
+
+    println!("debugging {}, type help for commands", path);
+    loop {
+        print!("(icdb) ");
+        sout.flush().unwrap();
+
+        let mut line = String::new();
+        if sin.read_line(&mut line).unwrap() == 0 || !dbg.execute(&line, &mut sout).unwrap() {
+            break;
+        }
+    }
+}
+
+fn trace(path: &str, trace: &str, ascii: bool) {
+    let mut sink = JsonTrace(BufWriter::new(File::create(trace).unwrap()));
+    let res = execute(read_prog(path), None, Some(&mut sink), ascii);
+
+    // exit() skips destructors, so the buffered trace has to be flushed here
+    sink.0.flush().unwrap();
+    finish(res)
+}
+
+fn record(path: &str, log: &str, ascii: bool) {
+    let mut recorder = Recorder::new();
+    let res = execute(read_prog(path), None, Some(&mut recorder), ascii);
+
+    let mut file = File::create(log).unwrap();
+    write!(file, "{}", recorder.session).unwrap();
+    finish(res)
+}
+
+fn replay(path: &str, log: &str, ascii: bool) {
+    let session = match Session::parse(&read_text(log)) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("{}: {}", log, e);
+            exit(-1);
+        }
+    };
+
+    // the recorded inputs are already character codes in an ASCII session,
+    // only the output is shown differently
+    let inputs: std::collections::VecDeque<Cell> = session.inputs().collect();
+    let mut replayer = Replayer::new(session);
+    let res = if ascii {
+        let mut o = AsciiOutput::new(BufWriter::new(stdout().lock()));
+        let res = replay_to(read_prog(path), inputs, &mut replayer, &mut o);
+        o.into_inner().flush().unwrap();
+        res
+    } else {
+        replay_to(read_prog(path), inputs, &mut replayer, &mut TextOutput(stdout()))
+    };
+
+    match replayer.finish() {
+        Ok(events) => eprintln!("replay matched {} events", events),
+        Err(mismatch) => {
+            eprintln!("replay diverged: {}", mismatch);
+            exit(-1);
+        }
+    }
+    finish(res)
+}
+
+fn replay_to<O: OutputPort<Cell>>(prog: Vec<Cell>, mut i: std::collections::VecDeque<Cell>, replayer: &mut Replayer<Cell>, o: &mut O) -> std::result::Result<Cell, IntcodeError<Cell>> {
+    let mut pc = IntCodePC::new_with_io(prog, &mut i, o);
+    pc.trace_to(replayer);
+    pc.run()
+}
+
+// writes either the hotness report followed by the annotated disassembly,
+// or folded call stacks for flamegraph.pl
+fn profile(path: &str, out: &str, folded: bool, ascii: bool) {
+    let prog: Vec<Cell> = read_prog(path);
+    let sin = stdin();
+    let mut sout = stdout();
+
+    let mut pc = machine(prog, &sin, &mut sout, ascii);
+    pc.enable_profile();
+    let res = run(&mut pc, ascii);
+    let profile = pc.take_profile().unwrap();
+
+    // decoded from the final memory, since self-modifying code may only
+    // form valid instructions after it ran
+    let mut file = File::create(out).unwrap();
+    if folded {
+        write!(file, "{}", profile.folded()).unwrap();
+    } else {
+        write!(file, "{}\n{}", profile.report(pc.memory(), 20), profile.annotate(pc.memory())).unwrap();
+    }
+    drop(pc);
+    finish(res)
+}
+
 fn main() {
-    let mut args = args();
-    match args.nth(1) {
-        Some(path) => {
-            let prog = read_prog(&path);
-            let status = execute(prog);
-            exit(status)
+    let mut args: Vec<String> = args().skip(1).collect();
+    let ascii = args.first().map(|a| a.as_str()) == Some("--ascii");
+    if ascii {
+        args.remove(0);
+    }
+    // --ascii only goes with the modes that run the program on stdin
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>().as_slice() {
+        ["disasm", path] if !ascii => disasm(path),
+        ["asm", path] if !ascii => asm(path),
+        ["compile", path] if !ascii => compile(path, "run"),
+        ["compile", path, name] if !ascii => compile(path, name),
+        ["analyze", path] if !ascii => analyze_prog(path, false),
+        ["analyze", "--dot", path] if !ascii => analyze_prog(path, true),
+        ["fuzz", opts @ .., path, dir] if !ascii => fuzz(path, dir, opts),
+        ["--debug", path] if !ascii => debug(path),
+        ["--trace", log, path] => trace(path, log, ascii),
+        ["--record", log, path] => record(path, log, ascii),
+        ["--replay", log, path] => replay(path, log, ascii),
+        ["--profile", out, path] => profile(path, out, false, ascii),
+        ["--folded", out, path] => profile(path, out, true, ascii),
+        ["--resume", state] => {
+            let mut file = BufReader::new(File::open(state).unwrap());
+            match Snapshot::read_from(&mut file) {
+                Ok(snap) => finish(execute(Vec::new(), Some(&snap), None, ascii)),
+                Err(e) => {
+                    eprintln!("{}: {}", state, e);
+                    exit(-1);
+                }
+            }
         },
-        None =>  {
+        [path] => finish(execute(read_prog(path), None, None, ascii)),
+        _ =>  {
             usage();
             exit(-1);
         }
@@ -30,8 +30,7 @@ impl Day1 {
 
     fn read_mases(&self) -> Vec<u32> {
         let path = self.input();
-        let mases = self.read_input_lines(&path, |l| l.parse::<u32>().unwrap());
-        mases
+        self.read_input_lines(&path, |l| l.parse::<u32>().unwrap())
     }
 
     fn fuel(&self, mass: u32) -> u32 {
@@ -1,6 +1,7 @@
 
 use crate::day::Day;
 use crate::intcode::{IntCodePC, read_program};
+use crate::intcode::symbolic::{find, Goal, Source};
 
 pub struct Day2 {
 
@@ -8,36 +9,26 @@ pub struct Day2 {
 
 impl Day for Day2 {
     fn first_puzzle(&self) -> String {
-        let program = read_program(self);
+        let program: Vec<i64> = read_program(self);
         let mut i = std::io::empty();
         let mut o = std::io::sink();
         let mut pc = IntCodePC::new(program, &mut i, &mut o);
         pc.alert1202();
-        let ret = pc.run();
+        let ret = pc.run().unwrap();
         format!("{}", ret)
     }
 
     fn second_puzzle(&self) -> String {
 
-        let program = read_program(self);
+        let program: Vec<i64> = read_program(self);
 
-        let mut i = std::io::empty();
-        let mut o = std::io::sink();
-        let mut pc = IntCodePC::new(program.clone(), &mut i, &mut o);
-
-        for noun in 0..99 {
-            for verb in 0 .. 99 {
-                pc.init(noun, verb);                
-                if pc.run() == 19690720 {
-                    let ret = 100 * noun + verb;
-                    return format!("{}", ret);
-                } else {
-                    pc.reset(program.clone());
-                }
-            }
+        // the result is linear in noun and verb, so this solves for them
+        // rather than trying all 10000 pairs
+        let sources = [Source::Cell(1), Source::Cell(2)];
+        match find(&program, &sources, &[0 ..= 99, 0 ..= 99], Goal::Memory(0), 19690720) {
+            Some(vals) => format!("{}", 100 * vals[0] + vals[1]),
+            None => panic!("19690720 not found")
         }
-
-        panic!("19690720 not found");
     }
 
     fn number(&self) -> u8 {
@@ -22,7 +22,7 @@ impl Day3 {
        (c1, c2)
     }
 
-    pub fn parse_curve(&self, line: &String) -> Curve {
+    pub fn parse_curve(&self, line: &str) -> Curve {
 
        let tokens: Vec<String> = line.split(',').map(|t| t.trim().to_owned()).collect();
 
@@ -55,7 +55,7 @@ impl Day3 {
                     p0 = p1;
                 }, 
                 _ => {
-                    panic!(format!("invalid token: {}", t));
+                    panic!("invalid token: {}", t);
                 }
             }
 
@@ -136,21 +136,11 @@ impl Segment {
     pub fn containes(&self, p: &Point) -> bool {
         match self.orientation() {
             Orientation::Horizontal => {
-                if p.y == self.start.y && self.min_x() < p.x && p.x < self.max_x() {
-                    true
-                }
-                else {
-                    false
-                }
+                p.y == self.start.y && self.min_x() < p.x && p.x < self.max_x()
             },
 
             Orientation::Vertical => {
-                if p.x == self.start.x && self.min_y() < p.y && p.y < self.max_y() {
-                    true
-                }
-                else {
-                    false
-                }
+                p.x == self.start.x && self.min_y() < p.y && p.y < self.max_y()
             }
         }
     }
@@ -183,6 +173,7 @@ impl Segment {
     }
 }
 
+#[derive(Default)]
 pub struct Curve {
     segments: Vec<Segment>
 }
@@ -234,7 +225,7 @@ impl Day for Day3 {
         let intersections = c1.intersection_points(&c2);
 
         let origin = Point::new(0, 0);
-        let mut min = u32::max_value();
+        let mut min = u32::MAX;
         for p in intersections.iter() {
             min =u32::min(min, p.dist(&origin));
         }
@@ -247,7 +238,7 @@ impl Day for Day3 {
         let (c1, c2) = self.read_curves();
         let intersections = c1.intersection_points(&c2);
 
-        let mut min = u32::max_value();
+        let mut min = u32::MAX;
         for p in intersections.iter() {
             let s1 = c1.steps_to_reach(p);
             let s2 = c2.steps_to_reach(p);
@@ -341,6 +332,7 @@ mod tests
 
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn curve_intersection() {
         let l0 = String::from("R8,U5,L5,D3");
         let c0 = DAY3.parse_curve(&l0);
@@ -21,7 +21,7 @@ impl Day4 {
     }
     
     fn is_six_digits(&self, num: u32) -> bool {
-        100000 <= num && num <= 999999
+        (100000..=999999).contains(&num)
     }
 
     fn has_adjacent_digits(&self, num: u32) -> bool {
@@ -150,6 +150,7 @@ mod tests
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn has_adjacent_digits_pair() {
         assert_eq!(DAY4.has_adjacent_digits_pair(112233), true);
         assert_eq!(DAY4.has_adjacent_digits_pair(123444), false);
@@ -1,34 +1,29 @@
 use crate::day::Day;
 use crate::intcode::{IntCodePC, read_program};
+use std::collections::VecDeque;
 
 pub struct Day5 {
 }
 
 impl Day for Day5 {
     fn first_puzzle(&self) -> String {
-        let program = read_program(self);
-        let buf = b"1";
-        let mut i = &buf[..];
-        let mut o: Vec<u8> = Vec::new();
-        let mut pc = IntCodePC::new(program, &mut i, &mut o);
-        pc.run();
-
-        let lines = String::from_utf8(o).unwrap();
-        let code = lines.split_whitespace().last().unwrap();
+        let program: Vec<i64> = read_program(self);
+        let mut i = VecDeque::from(vec![1]);
+        let mut code = 0;
+        let mut o = |v| code = v;
+        let mut pc = IntCodePC::new_with_io(program, &mut i, &mut o);
+        pc.run().unwrap();
 
         format!("{}", code)
     }
 
     fn second_puzzle(&self) -> String {
-        let program = read_program(self);
-        let buf = b"5";
-        let mut i = &buf[..];
-        let mut o: Vec<u8> = Vec::new();
-        let mut pc = IntCodePC::new(program, &mut i, &mut o);
-        pc.run();
-
-        let lines = String::from_utf8(o).unwrap();
-        let code = lines.split_whitespace().last().unwrap();
+        let program: Vec<i64> = read_program(self);
+        let mut i = VecDeque::from(vec![5]);
+        let mut code = 0;
+        let mut o = |v| code = v;
+        let mut pc = IntCodePC::new_with_io(program, &mut i, &mut o);
+        pc.run().unwrap();
 
         format!("{}", code)
     }
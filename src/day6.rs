@@ -23,24 +23,22 @@ impl Day for Day6 {
         let san = String::from("SAN");
         let me = String::from("YOU");
 
-        match graph.find_route(&me, &san) {
-            Some(me_to_san) => return format!("{}", me_to_san.len() - 2),
-            None => {}
-        };
+        if let Some(me_to_san) = graph.find_route(&me, &san) {
+            return format!("{}", me_to_san.len() - 2);
+        }
 
-        match graph.find_route(&san, &me) {
-            Some(san_to_me) => return format!("{}", san_to_me.len() - 2),
-            None => {}
-        };
+        if let Some(san_to_me) = graph.find_route(&san, &me) {
+            return format!("{}", san_to_me.len() - 2);
+        }
 
-        let mut min_trans = usize::max_value();
+        let mut min_trans = usize::MAX;
         for v in graph.vertices().filter(|&v| *v != san && *v != me) {
-            let v_to_san = graph.find_route(&v, &san);
+            let v_to_san = graph.find_route(v, &san);
             if v_to_san.is_none() {
                 continue;
             }
 
-            let v_to_me = graph.find_route(&v, &me);
+            let v_to_me = graph.find_route(v, &me);
             if v_to_me.is_none() {
                 continue;
             } 
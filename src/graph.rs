@@ -21,6 +21,10 @@ impl<Vertex, Weight> Graph<Vertex, Weight> where Vertex: PartialEq {
         self.add_edge_priv(start_vert, end_vert, weight);
     }
 
+    pub fn add_vertex(&mut self, v: Vertex) {
+        self.add_vert_priv(v);
+    }
+
     pub fn find_edge<'g>(&'g self, start: &Vertex, end: &Vertex) -> Option<Edge<'g, Vertex, Weight>> {
 
         let start_vert =  self.find_vert_priv(start);
@@ -46,7 +50,7 @@ impl<Vertex, Weight> Graph<Vertex, Weight> where Vertex: PartialEq {
         }
     }
 
-    pub fn vertices(&self) -> VertIter<Vertex> {
+    pub fn vertices(&self) -> VertIter<'_, Vertex> {
         VertIter {inner_iter: self.vertices.iter()}
     }
 
@@ -71,43 +75,36 @@ impl<Vertex, Weight> Graph<Vertex, Weight> where Vertex: PartialEq {
     }
 
     fn find_vert_priv(&self, v: &Vertex) -> Option<usize> {
-        match self.vertices.iter().find(|(_, vert)| *vert == v) {
-            Some((k, _)) => Some(*k),
-            None => None
-        }
+        self.vertices.iter().find(|(_, vert)| *vert == v).map(|(k, _)| *k)
     }
 
     fn get_vert_priv(&self, id: usize) -> &Vertex {
-        self.vertices.get(&id).expect(&format!("not existing id: {}", id))
+        self.vertices.get(&id).unwrap_or_else(|| panic!("not existing id: {}", id))
     }
 
     fn find_edge_priv<'g>(&'g self, start: usize, end: usize) -> Option<Edge<'g, Vertex, Weight>> {
         match self.edges.iter().find(|(s, e, _)| *s == start && *e == end) {
-            Some((s, e, w)) => Some((self.get_vert_priv(*s), self.get_vert_priv(*e), &w)),
+            Some((s, e, w)) => Some((self.get_vert_priv(*s), self.get_vert_priv(*e), w)),
             None => None
         }
     }
 
     fn find_route_priv<'g>(&'g self, start: usize, end: usize) -> Option<Vec<Edge<'g, Vertex,Weight>>> {
 
-        match self.find_edge_priv(start, end) {
-            Some(direct) => return Some(vec![direct]),
-            None => {}
+        if let Some(direct) = self.find_edge_priv(start, end) {
+            return Some(vec![direct]);
         }
 
         let mut route = Vec::new();
         for (s, e, w) in self.edges.iter().filter(|(s, _, _)| *s == start) {
-            match self.find_route_priv(*e, end) {
-                Some(mut sub_route) => {
-                    route.push((self.get_vert_priv(*s), self.get_vert_priv(*e), w));
-                    route.append(&mut sub_route);
-                    break; 
-                },
-                None => {}
+            if let Some(mut sub_route) = self.find_route_priv(*e, end) {
+                route.push((self.get_vert_priv(*s), self.get_vert_priv(*e), w));
+                route.append(&mut sub_route);
+                break; 
             }
         }
 
-        if route.len() != 0 {
+        if !route.is_empty() {
             Some(route)
         } else {
             None
@@ -115,6 +112,12 @@ impl<Vertex, Weight> Graph<Vertex, Weight> where Vertex: PartialEq {
     }
 } 
 
+impl<Vertex, Weight> Default for Graph<Vertex, Weight> where Vertex: PartialEq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct VertIter<'a, Vertex: PartialEq> {
     inner_iter: std::collections::hash_map::Iter<'a, usize, Vertex>
 }
@@ -189,6 +192,20 @@ mod tests
     }
 
     #[test]
+    fn add_vertex() {
+
+        let mut graph: Graph<String, usize> = Graph::new();
+        graph.add_vertex(String::from("A"));
+        graph.add_edge(String::from("A"), String::from("B"), 1);
+        graph.add_vertex(String::from("B"));
+        graph.add_vertex(String::from("C"));
+
+        assert_eq!(graph.vertices.len(), 3);
+        assert_eq!(graph.edges.len(), 1);
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn find_edge() {
 
         let (a, b, c) = (String::from("A"), String::from("B"), String::from("C"));
@@ -1,24 +1,76 @@
 use crate::day::Day;
-use std::error::Error;
 use std::io::prelude::*;
 use std::collections::VecDeque;
-use std::cell::RefCell;
 
+mod word;
+mod error;
+pub mod memory;
+pub mod io;
+pub mod network;
+pub mod disasm;
+pub mod asm;
+pub mod debugger;
+pub mod watch;
+pub mod trace;
+pub mod snapshot;
+pub mod journal;
+pub mod session;
+pub mod profile;
+pub mod cached;
+pub mod compile;
+pub mod analyze;
+pub mod symbolic;
+pub mod fuzz;
+pub mod limits;
+pub mod devices;
+
+pub use word::Word;
+pub use error::IntcodeError;
+pub use memory::Memory;
+pub use io::{AsciiInput, AsciiOutput, InputPort, OutputPort, TextInput, TextOutput};
+pub use watch::{Access, Hit};
+pub use trace::{Record, TraceSink};
+pub use snapshot::Snapshot;
+pub use journal::Journal;
+pub use profile::Profile;
+pub use cached::DecodeCache;
+pub use compile::Compiled;
+pub use analyze::Analysis;
+pub use symbolic::Symbolic;
+pub use fuzz::Fuzzer;
+pub use limits::Limits;
+pub use devices::{Bus, Device};
+
+enum Input<'i, W: Word> {
+    Stdin(&'i std::io::Stdin),
+    Text(TextInput<&'i mut dyn BufRead>),
+    Port(&'i mut dyn InputPort<W>),
+    Detached
+}
+
+enum Output<'o, W: Word> {
+    Text(TextOutput<&'o mut dyn Write>),
+    Port(&'o mut dyn OutputPort<W>),
+    Detached
+}
+
+pub type Result<T, W = i64> = std::result::Result<T, IntcodeError<W>>;
 
-pub fn read_program<D: Day>(day: &D) -> Vec<i32> {
+pub fn read_program<W: Word, D: Day>(day: &D) -> Vec<W> {
     let path = day.input();
     let lines = day.read_input_lines_string(&path);
-    let program = lines[0].split(',').map(|t| t.parse::<i32>().unwrap()).collect();
-    program
+    lines[0].split(',').map(|t| W::parse(t.trim()).unwrap()).collect()
 }
 
-enum Input<'i> {
-    Stdin(&'i std::io::Stdin),
-    External(RefCell<&'i mut dyn BufRead>)
+#[derive(PartialEq, Debug)]
+pub enum State<W: Word = i64> {
+    NeedsInput,
+    Output(W),
+    Halted(W)
 }
 
-#[derive(PartialEq, Debug)]
-pub enum Opcode {
+#[derive(Clone, PartialEq, Debug)]
+pub enum Opcode<W: Word = i64> {
     Add,
     Mul,
     Input,
@@ -27,188 +79,692 @@ pub enum Opcode {
     Jz,
     Le,
     Eq,
-    Halt(i32)
+    Arb,
+    Halt(W)
 }
 
-#[derive(PartialEq, Debug)]
+impl<W: Word> Opcode<W> {
+    pub fn code(&self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::Jnz => 5,
+            Opcode::Jz => 6,
+            Opcode::Le => 7,
+            Opcode::Eq => 8,
+            Opcode::Arb => 9,
+            Opcode::Halt(_) => 99
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        let code = self.code();
+        INSTRUCTIONS.iter().find(|(c, _, _)| *c == code).unwrap().1
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ParamMode {
     Position,
-    Immediate
+    Immediate,
+    Relative
 }
 
-pub struct IntCodePC<'i, 'o> {
-    program: Vec<i32>,
+// opcode, mnemonic and parameter count of every instruction, in Opcode order
+pub const INSTRUCTIONS: [(i64, &str, usize); 10] = [
+    (1, "add", 3),
+    (2, "mul", 3),
+    (3, "input", 1),
+    (4, "output", 1),
+    (5, "jnz", 2),
+    (6, "jz", 2),
+    (7, "le", 3),
+    (8, "eq", 3),
+    (9, "arb", 1),
+    (99, "halt", 0)
+];
+
+pub struct IntCodePC<'i, 'o, W: Word = i64> {
+    memory: Memory<W>,
     pc: usize,
+    rb: W,
+    ins_pc: usize,
+    ins: W,
     modes: VecDeque<ParamMode>,
-    i: Input<'i>,
-    o: &'o mut dyn Write
+    queue: VecDeque<W>,
+    yielding: bool,
+    yielded: Option<W>,
+    watches: watch::Watches<W>,
+    trace: Option<&'o mut dyn TraceSink<W>>,
+    record: Option<Record<W>>,
+    journal: Option<Journal<W>>,
+    undo: Option<journal::Step<W>>,
+    profile: Option<Profile>,
+    cache: Option<DecodeCache>,
+    guard: Option<limits::Guard<W>>,
+    bus: Bus<'o, W>,
+    i: Input<'i, W>,
+    o: Output<'o, W>
 }
 
-impl<'i, 'o> IntCodePC<'i, 'o> {
+impl<'i, 'o, W: Word> IntCodePC<'i, 'o, W> {
+
+    pub fn new<I: BufRead, O: Write>(program: Vec<W>, i: &'i mut I, o: &'o mut O) -> IntCodePC<'i, 'o, W> {
+        IntCodePC::build(program, Input::Text(TextInput(i)), Output::Text(TextOutput(o)))
+    }
+
+    pub fn new_with_stdin<O : Write>(program: Vec<W>, i: &'i std::io::Stdin,  o: &'o mut O) -> IntCodePC<'i, 'o, W> {
+        IntCodePC::build(program, Input::Stdin(i), Output::Text(TextOutput(o)))
+    }
 
-    pub fn new<I: BufRead, O: Write>(program: Vec<i32>, i: &'i mut I, o: &'o mut O) -> IntCodePC<'i, 'o> {
-        IntCodePC {program, pc:0, modes: VecDeque::new(), i: Input::External(RefCell::new(i)), o}
+    pub fn new_detached(program: Vec<W>) -> IntCodePC<'i, 'o, W> {
+        IntCodePC::build(program, Input::Detached, Output::Detached)
     }
 
-    pub fn new_with_stdin<O : Write>(program: Vec<i32>, i: &'i std::io::Stdin,  o: &'o mut O) -> IntCodePC<'i, 'o> {
-        IntCodePC {program, pc:0, modes: VecDeque::new(), i: Input::Stdin(i), o}
+    pub fn new_with_io<I, O>(program: Vec<W>, i: &'i mut I, o: &'o mut O) -> IntCodePC<'i, 'o, W>
+        where I: InputPort<W>, O: OutputPort<W> {
+        IntCodePC::build(program, Input::Port(i), Output::Port(o))
+    }
+
+    fn build(program: Vec<W>, i: Input<'i, W>, o: Output<'o, W>) -> IntCodePC<'i, 'o, W> {
+        IntCodePC {
+            memory: Memory::from(program),
+            pc: 0,
+            rb: W::zero(),
+            ins_pc: 0,
+            ins: W::zero(),
+            modes: VecDeque::new(),
+            queue: VecDeque::new(),
+            yielding: false,
+            yielded: None,
+            watches: watch::Watches::new(),
+            trace: None,
+            record: None,
+            journal: None,
+            undo: None,
+            profile: None,
+            cache: None,
+            guard: None,
+            bus: Bus::new(),
+            i,
+            o
+        }
     }
 
     pub fn alert1202(&mut self) {
-        self.init(12, 02);
+        self.init(W::from_i64(12), W::from_i64(2));
+    }
+
+    pub fn init(&mut self, noun: W, verb: W) {
+        self.memory.set(1, noun);
+        self.memory.set(2, verb);
+        self.forget(1);
+        self.forget(2);
+        self.forget_state();
     }
 
-    pub fn init(&mut self, noun: i32, verb: i32) {
-        self.program[1] = noun;
-        self.program[2] = verb;
+    pub fn reset(&mut self, program: Vec<W>) {
+        self.reset_memory(Memory::from(program));
     }
 
-    pub fn reset(&mut self, program: Vec<i32>) {
-        self.program = program;
+    pub fn reset_memory(&mut self, memory: Memory<W>) {
+        self.memory = memory;
         self.pc = 0;
+        self.rb = W::zero();
+        self.modes.clear();
+        self.queue.clear();
+        self.yielded = None;
+        self.watches.take_hits();
+        self.clear_journal();
+        self.clear_cache();
+        self.forget_state();
+    }
+
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.memory.clone(),
+            pc: self.pc,
+            rb: self.rb.clone(),
+            ins_pc: self.ins_pc,
+            ins: self.ins.clone(),
+            modes: self.modes.iter().cloned().collect(),
+            queue: self.queue.iter().cloned().collect(),
+            yielded: self.yielded.clone()
+        }
+    }
+
+    // puts the machine back in the state of the snapshot, keeping its I/O,
+    // watchpoints and trace sink
+    pub fn restore(&mut self, snap: &Snapshot<W>) {
+        self.memory = snap.memory.clone();
+        self.pc = snap.pc;
+        self.rb = snap.rb.clone();
+        self.ins_pc = snap.ins_pc;
+        self.ins = snap.ins.clone();
+        self.modes = snap.modes.iter().cloned().collect();
+        self.queue = snap.queue.iter().cloned().collect();
+        self.yielded = snap.yielded.clone();
+        self.watches.take_hits();
+        self.clear_journal();
+        self.clear_cache();
+        self.forget_state();
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    // edits made through this are not journaled, so the journal goes too:
+    // undoing past them would restore a state the machine was never in
+    pub fn memory_mut(&mut self) -> &mut Memory<W> {
+        self.clear_journal();
+        self.clear_cache();
+        self.forget_state();
+        &mut self.memory
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn rb(&self) -> &W {
+        &self.rb
+    }
+
+    // for code that runs part of a program outside the machine and hands
+    // the rest back to it
+    pub fn set_registers(&mut self, pc: usize, rb: W) {
+        self.pc = pc;
+        self.rb = rb;
+    }
+
+    // stores the way an instruction does, unlike memory_mut() which drops
+    // everything cached or journaled about the memory
+    pub fn store_word(&mut self, pos: usize, val: W) {
+        self.store(pos, val);
+    }
+
+    // whether anything looks at execution step by step: a trace sink, the
+    // journal, the profiler, limits, devices or watchpoints
+    pub fn has_hooks(&self) -> bool {
+        let watched = self.watches.watched(Access::Read).next().is_some() || self.watches.watched(Access::Write).next().is_some();
+        self.trace.is_some() || self.journal.is_some() || self.profile.is_some() || self.guard.is_some() || !self.bus.is_empty() || watched
+    }
+
+    pub fn watch(&mut self, addr: usize, access: Access) {
+        self.watches.watch(addr, access);
+    }
+
+    pub fn unwatch(&mut self, addr: usize, access: Access) -> bool {
+        self.watches.unwatch(addr, access)
+    }
+
+    pub fn watches(&self) -> &watch::Watches<W> {
+        &self.watches
+    }
+
+    // the watched accesses made since the last call, in execution order
+    pub fn take_hits(&mut self) -> Vec<Hit<W>> {
+        self.watches.take_hits()
+    }
+
+    // hands a record of every instruction executed from now on to the sink
+    pub fn trace_to(&mut self, sink: &'o mut dyn TraceSink<W>) {
+        self.trace = Some(sink);
+    }
+
+    // maps the device's cells from `base` onto it, so that instructions
+    // loading and storing there reach the device instead of memory; false
+    // if it would overlap a device already attached or run past the end of
+    // the address space. Like a trace sink, the device is borrowed for the
+    // lifetime of the output port.
+    pub fn attach(&mut self, base: usize, device: &'o mut dyn Device<W>) -> bool {
+        self.bus.attach(base, device)
+    }
+
+    pub fn stop_trace(&mut self) {
+        self.trace = None;
+    }
+
+    // keeps what is needed to undo each of the last `len` instructions
+    pub fn enable_journal(&mut self, len: usize) {
+        self.journal = Some(Journal::new(len));
+    }
+
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+        self.undo = None;
+    }
+
+    pub fn journal(&self) -> Option<&Journal<W>> {
+        self.journal.as_ref()
+    }
+
+    fn clear_journal(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+    }
+
+    // undoes the last journaled instruction; input it consumed goes back on
+    // the queue, output it produced stays produced
+    pub fn step_back(&mut self) -> bool {
+        let step = match self.journal.as_mut().and_then(|j| j.pop()) {
+            Some(step) => step,
+            None => return false
+        };
+
+        for (addr, old) in step.writes.into_iter().rev() {
+            self.memory.set(addr, old);
+            self.forget(addr);
+        }
+        self.memory.truncate(step.len);
+        self.pc = step.pc;
+        self.rb = step.rb;
+        if let Some(val) = step.input {
+            self.queue.push_front(val);
+        }
         self.modes.clear();
+        self.yielded = None;
+        self.forget_state();
+        true
     }
 
-    pub fn run(&mut self) -> i32 {
+    // steps back until the pc is `pc`, false if the journal ran out first
+    pub fn run_back_to(&mut self, pc: usize) -> bool {
+        while self.step_back() {
+            if self.pc == pc {
+                return true;
+            }
+        }
+        false
+    }
+
+    // steps back to just before the last instruction that wrote `addr`
+    pub fn run_back_to_write(&mut self, addr: usize) -> bool {
+        while let Some(wrote) = self.journal.as_ref().and_then(|j| j.last()).map(|s| s.wrote(addr)) {
+            self.step_back();
+            if wrote {
+                return true;
+            }
+        }
+        false
+    }
+
+    // counts what every instruction executed from now on does
+    pub fn enable_profile(&mut self) {
+        self.profile = Some(Profile::new());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    // runs instructions from a decoded form kept per address instead of
+    // decoding them each time; tracing, the journal and the profiler still
+    // go through the interpreter
+    pub fn enable_cache(&mut self) {
+        self.cache = Some(DecodeCache::new());
+    }
+
+    pub fn disable_cache(&mut self) {
+        self.cache = None;
+    }
+
+    pub fn cache(&self) -> Option<&DecodeCache> {
+        self.cache.as_ref()
+    }
+
+    fn clear_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+    }
+
+    // stops the machine with a distinct error when it runs past the limits;
+    // the step count starts over
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.guard = Some(limits::Guard::new(limits));
+    }
+
+    pub fn clear_limits(&mut self) {
+        self.guard = None;
+    }
+
+    pub fn limits(&self) -> Option<&Limits> {
+        self.guard.as_ref().map(|guard| &guard.limits)
+    }
+
+    // instructions executed since the limits were set
+    pub fn executed(&self) -> Option<u64> {
+        self.guard.as_ref().map(|guard| guard.steps())
+    }
+
+    // for loop detection, after I/O or changes made from outside
+    fn forget_state(&mut self) {
+        if let Some(guard) = &mut self.guard {
+            guard.forget();
+        }
+    }
+
+    fn check_limits(&mut self) -> Result<(), W> {
+        let exceeded = match &mut self.guard {
+            Some(guard) => guard.check(self.pc, &self.rb, &self.memory),
+            None => return Ok(())
+        };
+        self.ins_pc = self.pc;
+        self.ins = self.memory.get(self.pc);
+        let (pc, ins) = (self.ins_pc, self.ins.clone());
+        match exceeded {
+            None => Ok(()),
+            Some(limits::Exceeded::Steps) => Err(IntcodeError::StepLimit {pc, ins}),
+            Some(limits::Exceeded::Deadline) => Err(IntcodeError::Deadline {pc, ins}),
+            Some(limits::Exceeded::Loop) => Err(IntcodeError::InfiniteLoop {pc, ins})
+        }
+    }
+
+    fn forget(&mut self, pos: usize) {
+        if let Some(cache) = &mut self.cache {
+            cache.invalidate(pos);
+        }
+    }
+
+    pub fn run(&mut self) -> Result<W, W> {
         loop {
-            match self.step() {
-                Opcode::Halt(val) => return val,
+            if let Opcode::Halt(val) = self.step()? {
+                return Ok(val);
+            }
+        }
+    }
+
+    pub fn feed(&mut self, val: W) {
+        self.queue.push_back(val);
+    }
+
+    pub fn pending_input(&self) -> usize {
+        self.queue.len()
+    }
+
+    // runs until the machine halts, outputs a value or wants input that
+    // neither the queue nor its input port has
+    pub fn resume(&mut self) -> Result<State<W>, W> {
+        loop {
+            if self.queue.is_empty() && self.peek_opcode() == Some(3) {
+                self.ins_pc = self.pc;
+                self.ins = self.memory.get(self.pc);
+                match self.read_port()? {
+                    Some(val) => self.queue.push_back(val),
+                    None => return Ok(State::NeedsInput)
+                }
+            }
+
+            match self.step_yielding()? {
+                (Opcode::Halt(val), _) => return Ok(State::Halted(val)),
+                (_, Some(val)) => return Ok(State::Output(val)),
                 _ => {}
             }
         }
     }
 
-    pub fn step(&mut self) -> Opcode {
-        let op = self.op();
+    // executes one instruction like step(), but hands its output back instead
+    // of writing it, and leaves a halted or failed machine on the instruction
+    pub fn step_yielding(&mut self) -> Result<(Opcode<W>, Option<W>), W> {
+        self.yielding = true;
+        let op = self.step();
+        self.yielding = false;
+
         match op {
-            Opcode::Add => self.add(),
-            Opcode::Mul => self.mul(),
-            Opcode::Input => self.input(),
-            Opcode::Output => self.output(),
-            Opcode::Jnz => self.jnz(),
-            Opcode::Jz => self.jz(),
-            Opcode::Le => self.le(),
-            Opcode::Eq => self.eq(),
-            Opcode::Halt(_) => {}
+            Ok(Opcode::Halt(val)) => {
+                self.pc = self.ins_pc;
+                Ok((Opcode::Halt(val), None))
+            },
+            Ok(op) => Ok((op, self.yielded.take())),
+            Err(e) => {
+                self.pc = self.ins_pc;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn peek_opcode(&self) -> Option<i64> {
+        self.memory.get(self.pc).to_i64().map(|ins| ins % 100)
+    }
+
+    pub fn step(&mut self) -> Result<Opcode<W>, W> {
+        if self.guard.is_none() {
+            return self.dispatch();
+        }
+        self.check_limits()?;
+        let op = self.dispatch();
+        if let Ok(Opcode::Input) | Ok(Opcode::Output) = op {
+            self.forget_state();
         }
         op
     }
 
-    pub fn op(&mut self) -> Opcode {
-        let ins = self.read_imm();
+    fn dispatch(&mut self) -> Result<Opcode<W>, W> {
+        if self.trace.is_none() {
+            return match self.cache {
+                Some(_) if self.journal.is_none() && self.profile.is_none() => self.exec_cached(),
+                _ => self.exec()
+            };
+        }
+
+        self.record = Some(Record::new(self.pc, self.memory.get(self.pc), self.rb.clone()));
+        let res = self.exec();
+        let mut rec = self.record.take().unwrap();
+        if let Err(e) = &res {
+            rec.error = Some(e.to_string());
+        }
+
+        let written = self.trace.as_mut().unwrap().record(&rec);
+        match (res, written) {
+            (Ok(_), Err(error)) => Err(IntcodeError::Io {pc: self.ins_pc, ins: self.ins.clone(), error}),
+            (res, _) => res
+        }
+    }
+
+    fn exec(&mut self) -> Result<Opcode<W>, W> {
+        if self.journal.is_some() {
+            self.undo = Some(journal::Step::new(self.pc, self.rb.clone(), self.memory.len()));
+        }
+        let op = self.op()?;
+        if let Some(rec) = &mut self.record {
+            rec.opcode = Some(op.clone());
+        }
+        match op {
+            Opcode::Add => self.add()?,
+            Opcode::Mul => self.mul()?,
+            Opcode::Input => self.input()?,
+            Opcode::Output => self.output()?,
+            Opcode::Jnz => self.jnz()?,
+            Opcode::Jz => self.jz()?,
+            Opcode::Le => self.le()?,
+            Opcode::Eq => self.eq()?,
+            Opcode::Arb => self.arb()?,
+            Opcode::Halt(_) => {}
+        }
+        if let Some(undo) = self.undo.take() {
+            self.journal.as_mut().unwrap().push(undo);
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.step(self.ins_pc, self.ins.to_i64().unwrap(), self.pc);
+        }
+        Ok(op)
+    }
+
+    pub fn op(&mut self) -> Result<Opcode<W>, W> {
+        self.ins_pc = self.pc;
+        self.ins = self.read_imm();
+        let ins = match self.ins.to_i64() {
+            Some(ins) if ins >= 0 => ins,
+            _ => return Err(IntcodeError::UnknownOpcode {pc: self.ins_pc, ins: self.ins.clone()})
+        };
         let mut modes = ins / 100;
+        self.modes.clear();
         while modes > 0 {
-            if modes % 10 == 0 {
-                self.modes.push_back(ParamMode::Position);
-            } 
-            else {
-                self.modes.push_back(ParamMode::Immediate);
+            match modes % 10 {
+                0 => self.modes.push_back(ParamMode::Position),
+                1 => self.modes.push_back(ParamMode::Immediate),
+                2 => self.modes.push_back(ParamMode::Relative),
+                mode => return Err(IntcodeError::BadParamMode {pc: self.ins_pc, ins: self.ins.clone(), mode})
             }
 
             modes /= 10;
         }
 
         match ins % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Mul,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::Jnz,
-            6 => Opcode::Jz,
-            7 => Opcode::Le,
-            8 => Opcode::Eq,
-            99 => Opcode::Halt(self.halt()),
-            _ => panic!("invalid opcode")
-        }
-    }
-
-    pub fn add(&mut self) {
-        let sum = self.read() + self.read();
-        self.write(sum);
-    }
-
-    pub fn mul(&mut self) {
-        let prod = self.read() * self.read();
-        self.write(prod);
-    }
-
-    pub fn input(&mut self) {
-        let pos = self.read_imm();
-        let mut buf = String::new();
-        match self.read_line(&mut buf) {
-            Ok(_) => {
-                match buf.trim().parse::<i32>() {
-                    Ok(val) => {
-                        self.program[pos as usize] = val;
-                    },
-                    Err(e) => {
-                        panic!(format!("NaN: {} [len: {} buf: {}]", e.description(), buf.len(), buf))}
-                }
-            },
-            Err(e) => panic!(format!("Failed to read from cin: {}", e.description()))
+            1 => Ok(Opcode::Add),
+            2 => Ok(Opcode::Mul),
+            3 => Ok(Opcode::Input),
+            4 => Ok(Opcode::Output),
+            5 => Ok(Opcode::Jnz),
+            6 => Ok(Opcode::Jz),
+            7 => Ok(Opcode::Le),
+            8 => Ok(Opcode::Eq),
+            9 => Ok(Opcode::Arb),
+            99 => Ok(Opcode::Halt(self.halt())),
+            _ => Err(IntcodeError::UnknownOpcode {pc: self.ins_pc, ins: self.ins.clone()})
         }
+    }
 
+    pub fn add(&mut self) -> Result<(), W> {
+        let lhs = self.read()?;
+        let rhs = self.read()?;
+        match lhs.checked_add(&rhs) {
+            Some(sum) => self.write(sum),
+            None => Err(self.overflow())
+        }
+    }
+
+    pub fn mul(&mut self) -> Result<(), W> {
+        let lhs = self.read()?;
+        let rhs = self.read()?;
+        match lhs.checked_mul(&rhs) {
+            Some(prod) => self.write(prod),
+            None => Err(self.overflow())
+        }
     }
 
-    fn read_line(&self, buf: &mut String) -> std::io::Result<usize> {
-        match &self.i {
-            Input::Stdin(sin) => {
-                sin.read_line(buf)
+    pub fn input(&mut self) -> Result<(), W> {
+        let val = match self.queue.pop_front() {
+            Some(val) => Some(val),
+            None => self.read_port()?
+        };
+        match val {
+            Some(val) => {
+                if let Some(rec) = &mut self.record {
+                    rec.input = Some(val.clone());
+                }
+                if let Some(undo) = &mut self.undo {
+                    undo.input = Some(val.clone());
+                }
+                self.write(val)
             },
-            Input::External(rc) => {
-                rc.borrow_mut().read_line(buf)
+            None => {
+                Err(IntcodeError::InputExhausted {pc: self.ins_pc, ins: self.ins.clone()})
             }
         }
     }
 
+    // the next word from the input port, None when it has run out or the
+    // machine is detached
+    fn read_port(&mut self) -> Result<Option<W>, W> {
+        let val = match &mut self.i {
+            Input::Stdin(sin) => sin.read_word(),
+            Input::Text(text) => text.read_word(),
+            Input::Port(port) => port.read_word(),
+            Input::Detached => Ok(None)
+        };
+        match val {
+            Ok(val) => Ok(val),
+            Err(error) if error.kind() == std::io::ErrorKind::InvalidData => {
+                Err(IntcodeError::MalformedInput {pc: self.ins_pc, ins: self.ins.clone(), input: error.to_string()})
+            },
+            Err(error) => Err(IntcodeError::Io {pc: self.ins_pc, ins: self.ins.clone(), error})
+        }
+    }
+
+
+    pub fn output(&mut self) -> Result<(), W> {
+       let val = self.read()?; 
+       if let Some(rec) = &mut self.record {
+           rec.output = Some(val.clone());
+       }
+       if self.yielding {
+           self.yielded = Some(val);
+           return Ok(());
+       }
 
-    pub fn output(&mut self) {
-       let val = self.read(); 
-       match writeln!(self.o, "{}", val) {
-          Ok(_) => {},
-          Err(e) => panic!(format!("Failed to write to stdout: {}", e.description()))
+       let written = match &mut self.o {
+           Output::Text(text) => text.write_word(val),
+           Output::Port(port) => port.write_word(val),
+           Output::Detached => Ok(())
+       };
+       match written {
+          Ok(_) => Ok(()),
+          Err(error) => Err(IntcodeError::Io {pc: self.ins_pc, ins: self.ins.clone(), error})
        }
     }
 
-    pub fn jnz(&mut self) {
-        let cond = self.read();
-        let pos = self.read();
-        if cond != 0 {
-            self.pc = pos as usize;
+    pub fn jnz(&mut self) -> Result<(), W> {
+        let cond = self.read()?;
+        let pos = self.read()?;
+        if !cond.is_zero() {
+            self.pc = self.address(&pos)?;
         }
+        Ok(())
     }
 
-    pub fn jz(&mut self) {
-        let cond = self.read();
-        let pos = self.read();
-        if cond == 0 {
-            self.pc = pos as usize;
+    pub fn jz(&mut self) -> Result<(), W> {
+        let cond = self.read()?;
+        let pos = self.read()?;
+        if cond.is_zero() {
+            self.pc = self.address(&pos)?;
         }
+        Ok(())
     }
 
-    pub fn le(&mut self) {
-        let lhs = self.read();
-        let rhs = self.read();
+    pub fn le(&mut self) -> Result<(), W> {
+        let lhs = self.read()?;
+        let rhs = self.read()?;
         if lhs < rhs {
-            self.write(1);
+            self.write(W::one())
         } else {
-            self.write(0);
+            self.write(W::zero())
         }
     }
 
-    pub fn eq(&mut self) {
-        let lhs = self.read();
-        let rhs = self.read();
+    pub fn eq(&mut self) -> Result<(), W> {
+        let lhs = self.read()?;
+        let rhs = self.read()?;
 
         if rhs == lhs {
-            self.write(1);
+            self.write(W::one())
         } else {
-            self.write(0);
+            self.write(W::zero())
         }
     }
 
-    pub fn halt(&self) -> i32 {
-        self.program[0]
+    pub fn arb(&mut self) -> Result<(), W> {
+        let offset = self.read()?;
+        match self.rb.checked_add(&offset) {
+            Some(rb) => {
+                self.rb = rb;
+                Ok(())
+            },
+            None => Err(self.overflow())
+        }
+    }
+
+    pub fn halt(&self) -> W {
+        self.memory.get(0)
     }
 
     pub fn mode(&mut self) -> ParamMode {
@@ -218,33 +774,118 @@ impl<'i, 'o> IntCodePC<'i, 'o> {
         }
     }
 
-    pub fn read(&mut self) -> i32 {
-        match self.mode() {
-            ParamMode::Position => {
-                self.read_pos()
-            },
-            ParamMode::Immediate => {
-                self.read_imm()
-            }
+    pub fn read(&mut self) -> Result<W, W> {
+        let mode = self.mode();
+        let val = self.read_as(mode)?;
+        if let Some(rec) = &mut self.record {
+            rec.operands.push(val.clone());
+        }
+        Ok(val)
+    }
+
+    fn read_as(&mut self, mode: ParamMode) -> Result<W, W> {
+        match mode {
+            ParamMode::Position => self.read_pos(),
+            ParamMode::Immediate => Ok(self.read_imm()),
+            ParamMode::Relative => self.read_rel()
         }
     }
 
-    pub fn read_pos(&mut self) -> i32 {
-        let pos = self.program[self.pc];
+    pub fn read_pos(&mut self) -> Result<W, W> {
+        let pos = self.address(&self.memory.get(self.pc))?;
         self.pc += 1;
-        self.program[pos as usize]
+        Ok(self.load(pos))
     }
 
-    pub fn read_imm(&mut self) -> i32 {
-        let val = self.program[self.pc];
+    pub fn read_imm(&mut self) -> W {
+        let val = self.memory.get(self.pc);
         self.pc += 1;
         val
     }
 
-    pub fn write(&mut self, val: i32) {
-        let pos = self.program[self.pc];
-        self.program[pos as usize] = val;
+    pub fn read_rel(&mut self) -> Result<W, W> {
+        let pos = self.relative(&self.memory.get(self.pc))?;
         self.pc += 1;
+        Ok(self.load(pos))
+    }
+
+    pub fn write(&mut self, val: W) -> Result<(), W> {
+        let mode = self.mode();
+        self.write_as(mode, val)
+    }
+
+    fn write_as(&mut self, mode: ParamMode, val: W) -> Result<(), W> {
+        let pos = match mode {
+            ParamMode::Relative => self.relative(&self.memory.get(self.pc))?,
+            _ => self.address(&self.memory.get(self.pc))?
+        };
+        if let Some(max) = self.guard.as_ref().and_then(|guard| guard.limits.max_memory) {
+            if pos >= max {
+                return Err(IntcodeError::MemoryLimit {pc: self.ins_pc, ins: self.ins.clone(), addr: pos});
+            }
+        }
+        self.store(pos, val);
+        self.pc += 1;
+        Ok(())
+    }
+
+    // data accesses made by instructions go through load and store, so that
+    // watchpoints see them; instruction fetches and debugger edits do not
+    fn load(&mut self, pos: usize) -> W {
+        if !self.bus.is_empty() {
+            if let Some(val) = self.bus.read(pos) {
+                self.forget_state();
+                return val;
+            }
+        }
+        let val = self.memory.get(pos);
+        if self.watches.is_watched(pos, Access::Read) {
+            self.watches.record(Hit {pc: self.ins_pc, addr: pos, access: Access::Read, old: val.clone(), new: val.clone()});
+        }
+        val
+    }
+
+    fn store(&mut self, pos: usize, val: W) {
+        if !self.bus.is_empty() && self.bus.write(pos, val.clone()) {
+            self.forget_state();
+            return;
+        }
+        if self.watches.is_watched(pos, Access::Write) {
+            let old = self.memory.get(pos);
+            self.watches.record(Hit {pc: self.ins_pc, addr: pos, access: Access::Write, old, new: val.clone()});
+        }
+        if let Some(rec) = &mut self.record {
+            rec.writes.push((pos, val.clone()));
+        }
+        if let Some(undo) = &mut self.undo {
+            undo.writes.push((pos, self.memory.get(pos)));
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.wrote(val.to_i64());
+        }
+        if let Some(guard) = &mut self.guard {
+            guard.wrote(pos, &val);
+        }
+        self.forget(pos);
+        self.memory.set(pos, val);
+    }
+
+    fn relative(&self, offset: &W) -> Result<usize, W> {
+        match self.rb.checked_add(offset) {
+            Some(pos) => self.address(&pos),
+            None => Err(self.overflow())
+        }
+    }
+
+    fn address(&self, pos: &W) -> Result<usize, W> {
+        match pos.to_address() {
+            Some(addr) => Ok(addr),
+            None => Err(IntcodeError::BadAddress {pc: self.ins_pc, ins: self.ins.clone(), addr: pos.clone()})
+        }
+    }
+
+    fn overflow(&self) -> IntcodeError<W> {
+        IntcodeError::Overflow {pc: self.ins_pc, ins: self.ins.clone()}
     }
 
 }
@@ -261,8 +902,8 @@ mod tests
         let mut i = std::io::empty();
         let mut pc = IntCodePC::new(vec![1,9,10,3,2,3,11,0,99,30,40,50], &mut i, &mut o);
 
-        assert_eq!(pc.step(), Opcode::Add);
-        assert_eq!(pc.program, vec![1,9,10,70,2,3,11,0,99,30,40,50]);
+        assert_eq!(pc.step().unwrap(), Opcode::Add);
+        assert_eq!(pc.memory.to_vec(), vec![1,9,10,70,2,3,11,0,99,30,40,50]);
         assert_eq!(pc.pc, 4);
     }
 
@@ -275,8 +916,8 @@ mod tests
         let mut pc = IntCodePC::new(vec![1,9,10,70,2,3,11,0,99,30,40,50], &mut i, &mut o);
         pc.pc = 4;
 
-        assert_eq!(pc.step(), Opcode::Mul);
-        assert_eq!(pc.program, vec![3500,9,10,70,2,3,11,0,99,30,40,50]);
+        assert_eq!(pc.step().unwrap(), Opcode::Mul);
+        assert_eq!(pc.memory.to_vec(), vec![3500,9,10,70,2,3,11,0,99,30,40,50]);
         assert_eq!(pc.pc, 8);
     }
 
@@ -287,8 +928,8 @@ mod tests
         let mut i = &buf[..];
         let mut o = std::io::sink();
         let mut pc = IntCodePC::new(vec![3,2,0], &mut i, &mut o);
-        assert_eq!(pc.step(), Opcode::Input);
-        assert_eq!(vec![3, 2, 15], pc.program);
+        assert_eq!(pc.step().unwrap(), Opcode::Input);
+        assert_eq!(vec![3, 2, 15], pc.memory.to_vec());
     }
 
     #[test]
@@ -297,7 +938,7 @@ mod tests
         let mut i = std::io::empty();
         let mut o: Vec<u8> = Vec::new();
         let mut pc = IntCodePC::new(vec![4,2,15], &mut i, &mut o);
-        assert_eq!(pc.step(), Opcode::Output);
+        assert_eq!(pc.step().unwrap(), Opcode::Output);
         let s = String::from_utf8(o).unwrap();
         assert_eq!(s, "15\n");
     }
@@ -307,11 +948,11 @@ mod tests
         let mut o = std::io::sink();
         let mut i = std::io::empty();
         let mut pc = IntCodePC::new(vec![1105,1,9], &mut i, &mut o);
-        assert_eq!(pc.step(), Opcode::Jnz);
+        assert_eq!(pc.step().unwrap(), Opcode::Jnz);
         assert_eq!(pc.pc, 9);
 
         pc.reset(vec![1005,2, 0]);
-        assert_eq!(pc.step(), Opcode::Jnz);
+        assert_eq!(pc.step().unwrap(), Opcode::Jnz);
         assert_eq!(pc.pc, 3);
     }
 
@@ -320,11 +961,11 @@ mod tests
         let mut o = std::io::sink();
         let mut i = std::io::empty();
         let mut pc = IntCodePC::new(vec![1106,1,9], &mut i, &mut o);
-        assert_eq!(pc.step(), Opcode::Jz);
+        assert_eq!(pc.step().unwrap(), Opcode::Jz);
         assert_eq!(pc.pc, 3);
 
         pc.reset(vec![1006,2, 0]);
-        assert_eq!(pc.step(), Opcode::Jz);
+        assert_eq!(pc.step().unwrap(), Opcode::Jz);
         assert_eq!(pc.pc, 0);
     }
 
@@ -333,12 +974,12 @@ mod tests
         let mut o = std::io::sink();
         let mut i = std::io::empty();
         let mut pc = IntCodePC::new(vec![11107,1,9, 3], &mut i, &mut o);
-        assert_eq!(pc.step(), Opcode::Le);
-        assert_eq!(pc.program, vec![11107,1,9, 1]);
+        assert_eq!(pc.step().unwrap(), Opcode::Le);
+        assert_eq!(pc.memory.to_vec(), vec![11107,1,9, 1]);
 
-        pc.reset(vec![00007,4, 5, 6, 55, 66, 0]);
-        assert_eq!(pc.step(), Opcode::Le);
-        assert_eq!(pc.program, vec![00007,4, 5, 6, 55, 66, 1]);
+        pc.reset(vec![7,4, 5, 6, 55, 66, 0]);
+        assert_eq!(pc.step().unwrap(), Opcode::Le);
+        assert_eq!(pc.memory.to_vec(), vec![7,4, 5, 6, 55, 66, 1]);
     }
 
     #[test] 
@@ -346,12 +987,12 @@ mod tests
         let mut o = std::io::sink();
         let mut i = std::io::empty();
         let mut pc = IntCodePC::new(vec![11108, 1, 1, 3], &mut i, &mut o);
-        assert_eq!(pc.step(), Opcode::Eq);
-        assert_eq!(pc.program, vec![11108, 1, 1, 1]);
+        assert_eq!(pc.step().unwrap(), Opcode::Eq);
+        assert_eq!(pc.memory.to_vec(), vec![11108, 1, 1, 1]);
 
-        pc.reset(vec![00008, 4, 5, 6, 55, 55, 0]);
-        assert_eq!(pc.step(), Opcode::Eq);
-        assert_eq!(pc.program, vec![00008, 4, 5, 6, 55, 55, 1]);
+        pc.reset(vec![8, 4, 5, 6, 55, 55, 0]);
+        assert_eq!(pc.step().unwrap(), Opcode::Eq);
+        assert_eq!(pc.memory.to_vec(), vec![8, 4, 5, 6, 55, 55, 1]);
     }
 
     #[test]
@@ -362,7 +1003,7 @@ mod tests
         let mut pc = IntCodePC::new(vec![3500,9,10,70,2,3,11,0,99,30,40,50], &mut i, &mut o);
         pc.pc = 8;
 
-        assert_eq!(pc.step(), Opcode::Halt(3500));
+        assert_eq!(pc.step().unwrap(), Opcode::Halt(3500));
     }
 
     #[test]
@@ -372,19 +1013,19 @@ mod tests
         let mut o = std::io::sink();
 
         let mut pc = IntCodePC::new(vec![1,9,10,3,2,3,11,0,99,30,40,50], &mut i, &mut o);
-        assert_eq!(pc.run(), 3500);
+        assert_eq!(pc.run().unwrap(), 3500);
 
         let mut pc = IntCodePC::new(vec![1,0,0,0,99], &mut i, &mut o);
-        assert_eq!(pc.run(), 2);
+        assert_eq!(pc.run().unwrap(), 2);
 
         let mut pc = IntCodePC::new(vec![2,3,0,3,99], &mut i, &mut o);
-        assert_eq!(pc.run(), 2);
+        assert_eq!(pc.run().unwrap(), 2);
 
         let mut pc = IntCodePC::new(vec![2,4,4,5,99,0], &mut i, &mut o);
-        assert_eq!(pc.run(), 2);
+        assert_eq!(pc.run().unwrap(), 2);
 
         let mut pc = IntCodePC::new(vec![1,1,1,4,99,5,6,0,99], &mut i, &mut o);
-        assert_eq!(pc.run(), 30);
+        assert_eq!(pc.run().unwrap(), 30);
     }
 
     #[test]
@@ -392,7 +1033,248 @@ mod tests
         let mut i = std::io::empty();
         let mut o = std::io::sink();
         let mut pc = IntCodePC::new(vec![1002,4,3,4,33], &mut i, &mut o);
-        assert_eq!(Opcode::Mul, pc.step());
-        assert_eq!(vec![1002,4,3,4,99], pc.program);
+        assert_eq!(Opcode::Mul, pc.step().unwrap());
+        assert_eq!(vec![1002,4,3,4,99], pc.memory.to_vec());
+    }
+
+    #[test]
+    pub fn relative() {
+        let mut i = std::io::empty();
+        let mut o = std::io::sink();
+        let mut pc = IntCodePC::new(vec![109,7,22201,0,1,2,99,3,4,0], &mut i, &mut o);
+        assert_eq!(Opcode::Arb, pc.step().unwrap());
+        assert_eq!(pc.rb, 7);
+        assert_eq!(Opcode::Add, pc.step().unwrap());
+        assert_eq!(vec![109,7,22201,0,1,2,99,3,4,7], pc.memory.to_vec());
+
+        pc.reset(vec![109,-1,209,6,99,9]);
+        assert_eq!(Opcode::Arb, pc.step().unwrap());
+        assert_eq!(Opcode::Arb, pc.step().unwrap());
+        assert_eq!(pc.rb, 8);
+    }
+
+    #[test]
+    pub fn relative_input() {
+        let buf = b"42";
+        let mut i = &buf[..];
+        let mut o = std::io::sink();
+        let mut pc = IntCodePC::new(vec![109,3,203,1,0], &mut i, &mut o);
+        assert_eq!(Opcode::Arb, pc.step().unwrap());
+        assert_eq!(Opcode::Input, pc.step().unwrap());
+        assert_eq!(vec![109,3,203,1,42], pc.memory.to_vec());
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn relative_output() {
+        let mut i = std::io::empty();
+        let mut o: Vec<u8> = Vec::new();
+        let mut pc = IntCodePC::new(vec![109,2,204,2,99], &mut i, &mut o);
+        assert_eq!(Opcode::Arb, pc.step().unwrap());
+        assert_eq!(Opcode::Output, pc.step().unwrap());
+        assert_eq!(String::from_utf8(o).unwrap(), "99\n");
+    }
+
+    #[test]
+    pub fn large_numbers() {
+        let mut i = std::io::empty();
+        let mut o: Vec<u8> = Vec::new();
+        let mut pc = IntCodePC::new(vec![104,1125899906842624,99], &mut i, &mut o);
+        pc.run().unwrap();
+        assert_eq!(String::from_utf8(o).unwrap(), "1125899906842624\n");
+
+        let mut o: Vec<u8> = Vec::new();
+        let mut pc = IntCodePC::new(vec![1102,34915192,34915192,7,4,7,99,0], &mut i, &mut o);
+        pc.run().unwrap();
+        assert_eq!(String::from_utf8(o).unwrap(), "1219070632396864\n");
+    }
+
+    #[test]
+    pub fn overflow() {
+        let mut i = std::io::empty();
+        let mut o = std::io::sink();
+        let mut pc = IntCodePC::new(vec![1102,i64::MAX,2,0,99], &mut i, &mut o);
+        assert!(matches!(pc.run(), Err(IntcodeError::Overflow {pc: 0, ins: 1102})));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    pub fn bigint() {
+        use num_bigint::BigInt;
+
+        let mut i = std::io::empty();
+        let mut o: Vec<u8> = Vec::new();
+        let prog = vec![1102,i64::MAX,2,7,4,7,99,0].into_iter().map(BigInt::from).collect();
+        let mut pc: IntCodePC<BigInt> = IntCodePC::new(prog, &mut i, &mut o);
+        pc.run().unwrap();
+        assert_eq!(String::from_utf8(o).unwrap(), "18446744073709551614\n");
+    }
+
+    #[test]
+    pub fn memory_growth() {
+        let quine = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let mut i = std::io::empty();
+        let mut o: Vec<u8> = Vec::new();
+        let mut pc = IntCodePC::new(quine.clone(), &mut i, &mut o);
+        pc.run().unwrap();
+        assert_eq!(pc.memory().len(), 102);
+        assert_eq!(pc.memory().footprint().pages, 0);
+
+        let out: Vec<i64> = String::from_utf8(o).unwrap().lines().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(out, quine);
+    }
+
+    #[test]
+    pub fn sparse_memory() {
+        let mut i = std::io::empty();
+        let mut o: Vec<u8> = Vec::new();
+        let mut pc = IntCodePC::new(vec![1101,7,0,1000000000000,4,1000000000000,4,1000000000001,99], &mut i, &mut o);
+        pc.run().unwrap();
+        assert!(pc.memory().is_paged());
+        assert_eq!(pc.memory().footprint().pages, 2);
+
+        assert_eq!(String::from_utf8(o).unwrap(), "7\n0\n");
+    }
+
+    #[test]
+    pub fn errors() {
+        let mut i = std::io::empty();
+        let mut o = std::io::sink();
+        let mut pc = IntCodePC::new(vec![1101,1,1,5,42,0], &mut i, &mut o);
+        assert!(matches!(pc.run(), Err(IntcodeError::UnknownOpcode {pc: 4, ins: 42})));
+
+        pc.reset(vec![301,0,0,0,99]);
+        assert!(matches!(pc.step(), Err(IntcodeError::BadParamMode {pc: 0, ins: 301, mode: 3})));
+
+        pc.reset(vec![1,-1,0,0,99]);
+        assert!(matches!(pc.step(), Err(IntcodeError::BadAddress {pc: 0, ins: 1, addr: -1})));
+
+        pc.reset(vec![109,-5,1201,1,0,0,99]);
+        pc.step().unwrap();
+        assert!(matches!(pc.step(), Err(IntcodeError::BadAddress {pc: 2, ins: 1201, addr: -4})));
+
+        pc.reset(vec![3,0,99]);
+        assert!(matches!(pc.step(), Err(IntcodeError::InputExhausted {pc: 0, ins: 3})));
+    }
+
+    #[test]
+    pub fn malformed_input() {
+        let buf = b"abc\n";
+        let mut i = &buf[..];
+        let mut o = std::io::sink();
+        let mut pc = IntCodePC::new(vec![1101,0,0,0,3,0,99], &mut i, &mut o);
+        match pc.run() {
+            Err(IntcodeError::MalformedInput {pc, ins, input}) => {
+                assert_eq!((pc, ins, input.as_str()), (4, 3, "abc\n"));
+            },
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    pub fn resume() {
+        let mut pc = IntCodePC::new_detached(vec![3,9,8,9,10,9,4,9,99,-1,8]);
+        assert_eq!(pc.resume().unwrap(), State::NeedsInput);
+        assert_eq!(pc.resume().unwrap(), State::NeedsInput);
+
+        pc.feed(8);
+        assert_eq!(pc.resume().unwrap(), State::Output(1));
+        assert_eq!(pc.resume().unwrap(), State::Halted(3));
+        assert_eq!(pc.resume().unwrap(), State::Halted(3));
+    }
+
+    #[test]
+    pub fn resume_cooperative() {
+        let doubler = vec![3,11,1002,11,2,11,4,11,1105,1,0,0];
+        let mut a = IntCodePC::new_detached(doubler.clone());
+        let mut b = IntCodePC::new_detached(doubler);
+
+        let mut val = 1;
+        for _ in 0..5 {
+            a.feed(val);
+            if let State::Output(out) = a.resume().unwrap() {
+                b.feed(out);
+            }
+            if let State::Output(out) = b.resume().unwrap() {
+                val = out;
+            }
+        }
+
+        assert_eq!(val, 1024);
+        assert_eq!(a.resume().unwrap(), State::NeedsInput);
+    }
+
+    #[test]
+    pub fn resume_with_port() {
+        // doubles what comes in until the port runs out
+        let doubler = vec![3,11,1002,11,2,11,4,11,1105,1,0,0];
+        let mut i = std::collections::VecDeque::from(vec![3, 5]);
+        let mut out: Vec<i64> = Vec::new();
+        let mut pc = IntCodePC::new_with_io(doubler, &mut i, &mut out);
+        assert_eq!(pc.resume().unwrap(), State::Output(6));
+        assert_eq!(pc.resume().unwrap(), State::Output(10));
+        assert_eq!(pc.resume().unwrap(), State::NeedsInput);
+        pc.feed(1);
+        assert_eq!(pc.resume().unwrap(), State::Output(2));
+
+        let mut i: &[u8] = b"x\n";
+        let mut o = std::io::sink();
+        let mut pc = IntCodePC::new(vec![3,0,99], &mut i, &mut o);
+        assert!(matches!(pc.resume(), Err(IntcodeError::MalformedInput {pc: 0, ins: 3, ..})));
+    }
+
+    #[test]
+    pub fn queued_input() {
+        let mut i = std::io::empty();
+        let mut o: Vec<u8> = Vec::new();
+        let mut pc = IntCodePC::new(vec![3,0,4,0,99], &mut i, &mut o);
+        pc.feed(7);
+        assert_eq!(pc.run().unwrap(), 7);
+        assert_eq!(String::from_utf8(o).unwrap(), "7\n");
+    }
+
+    #[test]
+    pub fn typed_io() {
+        let mut i: VecDeque<i64> = vec![3, 4].into_iter().collect();
+        let mut o: Vec<i64> = Vec::new();
+        let mut pc = IntCodePC::new_with_io(vec![3,13,3,14,2,13,14,15,4,15,4,13,99,0,0,0], &mut i, &mut o);
+        pc.run().unwrap();
+        assert_eq!(o, vec![12, 3]);
+    }
+
+    #[test]
+    pub fn wired_io() {
+        let (mut tx, mut rx) = std::sync::mpsc::channel();
+        let mut i = TextInput(std::io::empty());
+        let mut a = IntCodePC::new_with_io(vec![104,21,104,-4,99], &mut i, &mut tx);
+        a.run().unwrap();
+        drop(tx);
+
+        let mut sum = 0;
+        let mut o = |v| sum += v;
+        let mut b = IntCodePC::new_with_io(vec![3,13,3,14,1,13,14,15,4,15,99,0,0,0,0,0], &mut rx, &mut o);
+        b.run().unwrap();
+        assert_eq!(sum, 17);
+    }
+
+    #[test]
+    pub fn watchpoints() {
+        // [9] = [9] + 1, twice, then output it; the operand fetch at 1 is not a read of [9]
+        let mut pc = IntCodePC::new_detached(vec![1001,9,1,9,1001,9,1,9,99,5]);
+        pc.watch(9, Access::Write);
+        pc.watch(1, Access::Read);
+        pc.run().unwrap();
+        assert_eq!(pc.take_hits(), vec![
+            Hit {pc: 0, addr: 9, access: Access::Write, old: 5, new: 6},
+            Hit {pc: 4, addr: 9, access: Access::Write, old: 6, new: 7},
+        ]);
+
+        pc.reset(vec![1001,9,1,9,1001,9,1,9,99,5]);
+        assert!(pc.unwatch(9, Access::Write));
+        pc.watch(9, Access::Read);
+        pc.run().unwrap();
+        let hits = pc.take_hits();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].to_string(), "[9] read at 4: 6");
+        assert!(pc.take_hits().is_empty());
+    }
+}
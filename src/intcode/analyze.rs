@@ -0,0 +1,349 @@
+use super::{ParamMode, Word};
+use super::disasm::{decode, label, Instruction};
+use crate::graph::Graph;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+// Code is found by following control flow from address 0 like the
+// disassembler does, except that branches on a constant only go the way the
+// constant sends them; what is only reachable the other way is dead code.
+// A jump to an immediate target right after an instruction that computes
+// the address following the jump from immediates is taken to be a call,
+// which returns there. Jumps to computed targets, such as returns, have no
+// known successors.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Flow {
+    Next,
+    Jump,
+    Return,
+    // a branch its constant condition never takes
+    Never
+}
+
+impl fmt::Display for Flow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Flow::Next => write!(f, "next"),
+            Flow::Jump => write!(f, "jump"),
+            Flow::Return => write!(f, "return"),
+            Flow::Never => write!(f, "never")
+        }
+    }
+}
+
+// the instructions from start up to end, entered only at start and left
+// only after the last one
+#[derive(Clone, PartialEq, Debug)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub dead: bool
+}
+
+// an instruction at pc storing to a fixed address inside the instruction at ins
+#[derive(Clone, PartialEq, Debug)]
+pub struct CodeWrite {
+    pub pc: usize,
+    pub addr: usize,
+    pub ins: usize
+}
+
+pub struct Analysis<W: Word = i64> {
+    pub code: BTreeMap<usize, Instruction<W>>,
+    pub dead: BTreeMap<usize, Instruction<W>>,
+    pub blocks: BTreeMap<usize, Block>,
+    // vertices are block starts
+    pub cfg: Graph<usize, Flow>,
+    pub inputs: Vec<usize>,
+    pub outputs: Vec<usize>,
+    pub indirect: Vec<usize>,
+    pub code_writes: Vec<CodeWrite>
+}
+
+fn is_jump<W: Word>(ins: &Instruction<W>) -> bool {
+    ins.opcode == 5 || ins.opcode == 6
+}
+
+fn is_call<W: Word>(image: &[W], ins: &Instruction<W>) -> bool {
+    let ret = W::from_i64(ins.addr as i64 + 3);
+    let push = match ins.addr.checked_sub(4).and_then(|addr| decode(image, addr)) {
+        Some(push) => push,
+        None => return false
+    };
+    let (a, b) = match push.operands.as_slice() {
+        [a, b, _] if a.mode == ParamMode::Immediate && b.mode == ParamMode::Immediate => (&a.value, &b.value),
+        _ => return false
+    };
+    let val = match push.opcode {
+        1 => a.checked_add(b),
+        2 => a.checked_mul(b),
+        _ => None
+    };
+    ins.target().is_some() && !ins.falls_through() && val == Some(ret)
+}
+
+// where control can go after `ins`, and where its constant condition keeps
+// it from going
+fn successors<W: Word>(image: &[W], ins: &Instruction<W>) -> (Vec<(usize, Flow)>, Option<usize>) {
+    let next = ins.addr + ins.width();
+    if ins.opcode == 99 {
+        return (Vec::new(), None);
+    }
+    if !is_jump(ins) {
+        return (vec![(next, Flow::Next)], None);
+    }
+
+    let cond = &ins.operands[0];
+    let taken = cond.mode != ParamMode::Immediate || cond.value.is_zero() == (ins.opcode == 6);
+    let mut succ = Vec::new();
+    let mut never = None;
+    match ins.target() {
+        Some(target) if taken => succ.push((target, Flow::Jump)),
+        Some(target) => never = Some(target),
+        None => {}
+    }
+    if ins.falls_through() {
+        succ.push((next, Flow::Next));
+    }
+    if is_call(image, ins) {
+        succ.push((next, Flow::Return));
+    }
+    (succ, never)
+}
+
+// decodes everything reachable from `starts` that does not overlap code
+// found before, collecting the targets of branches that are never taken
+fn explore<W: Word>(image: &[W], starts: Vec<usize>, claimed: &mut BTreeSet<usize>, code: &mut BTreeMap<usize, Instruction<W>>) -> Vec<usize> {
+    let mut work = starts;
+    let mut never = Vec::new();
+    while let Some(addr) = work.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+        let ins = match decode(image, addr) {
+            Some(ins) => ins,
+            None => continue
+        };
+        if (addr .. addr + ins.width()).any(|a| claimed.contains(&a)) {
+            continue;
+        }
+        claimed.extend(addr .. addr + ins.width());
+
+        let (succ, target) = successors(image, &ins);
+        work.extend(succ.into_iter().map(|(to, _)| to));
+        never.extend(target);
+        code.insert(addr, ins);
+    }
+    never
+}
+
+pub fn analyze<W: Word>(image: &[W]) -> Analysis<W> {
+    let mut claimed = BTreeSet::new();
+    let mut code = BTreeMap::new();
+    let mut dead = BTreeMap::new();
+    let mut never = explore(image, vec![0], &mut claimed, &mut code);
+    while !never.is_empty() {
+        never = explore(image, never, &mut claimed, &mut dead);
+    }
+
+    let all: BTreeMap<usize, (&Instruction<W>, bool)> = code.iter().map(|(&a, i)| (a, (i, false)))
+        .chain(dead.iter().map(|(&a, i)| (a, (i, true)))).collect();
+
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    for (ins, _) in all.values() {
+        let (succ, target) = successors(image, ins);
+        leaders.extend(succ.into_iter().filter(|&(_, flow)| flow != Flow::Next || is_jump(ins)).map(|(to, _)| to));
+        leaders.extend(target);
+    }
+
+    let mut blocks: BTreeMap<usize, Block> = BTreeMap::new();
+    let mut open: Option<usize> = None;
+    for (&addr, &(ins, dead)) in all.iter() {
+        let current = open.and_then(|start| blocks.get_mut(&start)).filter(|b| b.end == addr && b.dead == dead && !leaders.contains(&addr));
+        match current {
+            Some(block) => block.end += ins.width(),
+            None => {
+                blocks.insert(addr, Block {start: addr, end: addr + ins.width(), dead});
+                open = Some(addr);
+            }
+        }
+        if is_jump(ins) || ins.opcode == 99 {
+            open = None;
+        }
+    }
+
+    let mut cfg = Graph::new();
+    for block in blocks.values() {
+        cfg.add_vertex(block.start);
+        let (_, &(last, _)) = all.range(.. block.end).next_back().unwrap();
+        let (succ, target) = successors(image, last);
+        let edges = succ.into_iter().chain(target.map(|to| (to, Flow::Never)));
+        for (to, flow) in edges.filter(|(to, _)| blocks.contains_key(to)) {
+            cfg.add_edge(block.start, to, flow);
+        }
+    }
+
+    let io = |opcode: i64| code.values().filter(|ins| ins.opcode == opcode).map(|ins| ins.addr).collect();
+    let inputs = io(3);
+    let outputs = io(4);
+    let indirect = code.values().filter(|ins| is_jump(ins) && ins.target().is_none()).map(|ins| ins.addr).collect();
+
+    let mut code_writes = Vec::new();
+    for ins in code.values() {
+        let dest = match ins.opcode {
+            1 | 2 | 7 | 8 => &ins.operands[2],
+            3 => &ins.operands[0],
+            _ => continue
+        };
+        let addr = match dest.value.to_address() {
+            Some(addr) if dest.mode != ParamMode::Relative => addr,
+            _ => continue
+        };
+        if let Some((_, &(hit, _))) = all.range(..= addr).next_back().filter(|(_, (hit, _))| hit.addr + hit.width() > addr) {
+            code_writes.push(CodeWrite {pc: ins.addr, addr, ins: hit.addr});
+        }
+    }
+
+    Analysis {code, dead, blocks, cfg, inputs, outputs, indirect, code_writes}
+}
+
+impl<W: Word> Analysis<W> {
+
+    pub fn is_reachable(&self, addr: usize) -> bool {
+        self.code.contains_key(&addr)
+    }
+
+    fn instructions(&self, block: &Block) -> impl Iterator<Item = &Instruction<W>> {
+        let code = if block.dead { &self.dead } else { &self.code };
+        code.range(block.start .. block.end).map(|(_, ins)| ins)
+    }
+
+    pub fn report(&self) -> String {
+        let addrs = |addrs: &[usize]| addrs.iter().map(|a| format!("{:04}", a)).collect::<Vec<_>>().join(", ");
+        let labels: BTreeSet<usize> = self.blocks.keys().cloned().collect();
+        let mut out = String::new();
+
+        writeln!(out, "{} blocks, {} reachable and {} dead instructions", self.blocks.len(), self.code.len(), self.dead.len()).unwrap();
+        if !self.inputs.is_empty() {
+            writeln!(out, "input at {}", addrs(&self.inputs)).unwrap();
+        }
+        if !self.outputs.is_empty() {
+            writeln!(out, "output at {}", addrs(&self.outputs)).unwrap();
+        }
+        if !self.indirect.is_empty() {
+            writeln!(out, "computed jumps at {}", addrs(&self.indirect)).unwrap();
+        }
+        if !self.code_writes.is_empty() {
+            writeln!(out, "writes into code:").unwrap();
+            for w in self.code_writes.iter() {
+                writeln!(out, "    {:04} writes [{}], in the instruction at {:04}", w.pc, w.addr, w.ins).unwrap();
+            }
+        }
+        if !self.dead.is_empty() {
+            writeln!(out, "dead code:").unwrap();
+            for ins in self.dead.values() {
+                writeln!(out, "    {:04}: {}", ins.addr, ins.render(&labels)).unwrap();
+            }
+        }
+        out
+    }
+
+    // the control-flow graph for Graphviz, dead blocks dashed
+    pub fn to_dot(&self) -> String {
+        let labels: BTreeSet<usize> = self.blocks.keys().cloned().collect();
+        let mut out = String::new();
+
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in self.blocks.values() {
+            let lines: String = self.instructions(block).map(|ins| format!("{:04}: {}\\l", ins.addr, ins.render(&labels))).collect();
+            let style = if block.dead { ", style=dashed" } else { "" };
+            writeln!(out, "    {} [label=\"{}:\\l{}\"{}];", label(block.start), label(block.start), lines, style).unwrap();
+        }
+        for (from, to, flow) in self.cfg.edges() {
+            let style = match flow {
+                Flow::Return => ", style=dashed",
+                Flow::Never => ", style=dotted",
+                _ => ""
+            };
+            writeln!(out, "    {} -> {} [label=\"{}\"{}];", label(*from), label(*to), flow, style).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    // doubles and prints its input until it reaches zero
+    const SRC: &str = "
+            arb #stack
+            input [n]
+            jz [n], #done
+        again:
+            add #back, #0, rb+0
+            jnz #1, #double
+        back:
+            output [n]
+            add [n], #-1, [n]
+            jnz [n], #again
+        done:
+            add #99, #0, [patch]
+        patch:
+            output #0
+            jz #1, #never
+            halt
+        never:
+            output #-1
+            halt
+        double:
+            mul [n], #2, [n]
+            jz #0, rb+0
+        n:  data 0
+        stack:
+    ";
+
+    #[test]
+    fn blocks() {
+        let prog = assemble(SRC).unwrap();
+        let a = analyze(&prog);
+        let starts: Vec<usize> = a.blocks.keys().cloned().collect();
+        assert_eq!(starts, vec![0, 7, 14, 23, 32, 33, 36]);
+        assert_eq!(a.blocks[&23], Block {start: 23, end: 32, dead: false});
+        assert_eq!(a.blocks[&33], Block {start: 33, end: 36, dead: true});
+
+        assert!(a.is_reachable(36) && a.is_reachable(16));
+        assert!(!a.is_reachable(33));
+        assert_eq!(a.inputs, vec![2]);
+        assert_eq!(a.outputs, vec![14, 27]);
+        assert_eq!(a.indirect, vec![40]);
+        assert_eq!(a.code_writes, vec![CodeWrite {pc: 23, addr: 27, ins: 27}]);
+    }
+
+    #[test]
+    fn cfg() {
+        let prog = assemble(SRC).unwrap();
+        let a = analyze(&prog);
+        assert_eq!(a.cfg.find_edge(&0, &23).map(|e| *e.2), Some(Flow::Jump));
+        assert_eq!(a.cfg.find_edge(&0, &7).map(|e| *e.2), Some(Flow::Next));
+        assert_eq!(a.cfg.find_edge(&7, &36).map(|e| *e.2), Some(Flow::Jump));
+        assert_eq!(a.cfg.find_edge(&7, &14).map(|e| *e.2), Some(Flow::Return));
+        assert_eq!(a.cfg.find_edge(&23, &33).map(|e| *e.2), Some(Flow::Never));
+        assert!(a.cfg.find_edge(&36, &14).is_none());
+        assert_eq!(a.cfg.vertices().count(), 7);
+
+        let dot = a.to_dot();
+        assert!(dot.contains("    l33 [label=\"l33:\\l0033: output #-1\\l0035: halt\\l\", style=dashed];\n"), "{}", dot);
+        assert!(dot.contains("    l7 -> l14 [label=\"return\", style=dashed];\n"), "{}", dot);
+
+        let report = a.report();
+        assert!(report.starts_with("7 blocks, 14 reachable and 2 dead instructions\n"), "{}", report);
+        assert!(report.contains("    0023 writes [27], in the instruction at 0027\n"), "{}", report);
+    }
+}
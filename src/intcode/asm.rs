@@ -0,0 +1,360 @@
+use super::INSTRUCTIONS;
+use std::collections::HashMap;
+use std::fmt;
+
+// Source syntax, one statement per line:
+//
+//     ; comment
+//     name = expr              constant
+//     label:                   label, may precede a statement on the same line
+//     add [a], #5, rb-1        instruction, [position] #immediate rb+relative
+//     data 1, 2, end - start   raw words
+//
+// Expressions are integers, labels and constants combined with + - * and
+// parentheses; labels may be used before they are defined.
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Name(String),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>)
+}
+
+#[derive(Debug)]
+enum Statement {
+    Ins(i64, Vec<(i64, Expr)>),
+    Data(Vec<Expr>),
+    Const(String, Expr)
+}
+
+impl Statement {
+    fn len(&self) -> usize {
+        match self {
+            Statement::Ins(_, operands) => 1 + operands.len(),
+            Statement::Data(words) => words.len(),
+            Statement::Const(_, _) => 0
+        }
+    }
+}
+
+pub fn assemble(src: &str) -> Result<Vec<i64>, AsmError> {
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+
+    for (n, text) in src.lines().enumerate() {
+        let line = n + 1;
+        let err = |msg: String| AsmError {line, msg};
+        let mut text = text.split(';').next().unwrap().trim();
+
+        while let Some((name, rest)) = split_label(text) {
+            if labels.insert(String::from(name), addr as i64).is_some() {
+                return Err(err(format!("duplicate label {}", name)));
+            }
+            text = rest;
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(text).map_err(err)?;
+        addr += statement.len();
+        statements.push((line, statement));
+    }
+
+    let mut names = labels;
+    for (line, statement) in statements.iter() {
+        if let Statement::Const(name, expr) = statement {
+            let val = eval(expr, &names).map_err(|msg| AsmError {line: *line, msg})?;
+            if names.insert(name.clone(), val).is_some() {
+                return Err(AsmError {line: *line, msg: format!("duplicate name {}", name)});
+            }
+        }
+    }
+
+    let mut prog = Vec::new();
+    for (line, statement) in statements.iter() {
+        let err = |msg: String| AsmError {line: *line, msg};
+        match statement {
+            Statement::Ins(opcode, operands) => {
+                let mut ins = *opcode;
+                let mut scale = 100;
+                for (mode, _) in operands.iter() {
+                    ins += scale * mode;
+                    scale *= 10;
+                }
+                prog.push(ins);
+                for (_, expr) in operands.iter() {
+                    prog.push(eval(expr, &names).map_err(err)?);
+                }
+            },
+            Statement::Data(words) => {
+                for expr in words.iter() {
+                    prog.push(eval(expr, &names).map_err(err)?);
+                }
+            },
+            Statement::Const(_, _) => {}
+        }
+    }
+
+    Ok(prog)
+}
+
+// the comma separated form read_program and intcodepc load
+pub fn to_text(prog: &[i64]) -> String {
+    let words: Vec<String> = prog.iter().map(|w| w.to_string()).collect();
+    words.join(",")
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let colon = text.find(':')?;
+    let name = text[.. colon].trim();
+    if is_name(name) {
+        Some((name, text[colon + 1 ..].trim()))
+    } else {
+        None
+    }
+}
+
+fn parse_statement(text: &str) -> Result<Statement, String> {
+    if let Some(eq) = text.find('=') {
+        let name = text[.. eq].trim();
+        if !is_name(name) {
+            return Err(format!("invalid constant name {:?}", name));
+        }
+        return Ok(Statement::Const(String::from(name), parse_expr(&text[eq + 1 ..])?));
+    }
+
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(ws) => (&text[.. ws], text[ws ..].trim()),
+        None => (text, "")
+    };
+    let args: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(|a| a.trim()).collect()
+    };
+
+    if mnemonic == "data" {
+        let words = args.iter().map(|a| parse_expr(a)).collect::<Result<Vec<_>, _>>()?;
+        if words.is_empty() {
+            return Err(String::from("data without values"));
+        }
+        return Ok(Statement::Data(words));
+    }
+
+    let &(opcode, _, arity) = INSTRUCTIONS.iter().find(|(_, m, _)| *m == mnemonic)
+        .ok_or_else(|| format!("unknown mnemonic {:?}", mnemonic))?;
+    if args.len() != arity {
+        return Err(format!("{} takes {} operands, got {}", mnemonic, arity, args.len()));
+    }
+
+    let operands = args.iter().map(|a| parse_operand(a)).collect::<Result<Vec<_>, _>>()?;
+    Ok(Statement::Ins(opcode, operands))
+}
+
+fn parse_operand(text: &str) -> Result<(i64, Expr), String> {
+    if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return Ok((0, parse_expr(inner)?));
+    }
+    if let Some(imm) = text.strip_prefix('#') {
+        return Ok((1, parse_expr(imm)?));
+    }
+    if let Some(offset) = text.strip_prefix("rb") {
+        let offset = offset.trim();
+        if offset.is_empty() {
+            return Ok((2, Expr::Num(0)));
+        }
+        if offset.starts_with('+') || offset.starts_with('-') {
+            return Ok((2, parse_expr(offset)?));
+        }
+    }
+    Err(format!("operand {:?} needs a mode: [addr], #value or rb+offset", text))
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let tokens = tokenize(text)?;
+    let mut pos = 0;
+    let expr = parse_sum(&tokens, &mut pos)?;
+    match tokens.get(pos) {
+        None => Ok(expr),
+        Some(t) => Err(format!("unexpected {:?} in {:?}", t, text.trim()))
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if "+-*()".contains(c) {
+            tokens.push(c.to_string());
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(chars[start .. i].iter().collect());
+        } else {
+            return Err(format!("unexpected character {:?}", c));
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err(String::from("missing expression"));
+    }
+    Ok(tokens)
+}
+
+fn parse_sum(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+    let mut lhs = parse_product(tokens, pos)?;
+    while let Some(op) = tokens.get(*pos).filter(|t| *t == "+" || *t == "-") {
+        *pos += 1;
+        let rhs = parse_product(tokens, pos)?;
+        lhs = if op == "+" {
+            Expr::Add(Box::new(lhs), Box::new(rhs))
+        } else {
+            Expr::Sub(Box::new(lhs), Box::new(rhs))
+        };
+    }
+    Ok(lhs)
+}
+
+fn parse_product(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+    let mut lhs = parse_unary(tokens, pos)?;
+    while tokens.get(*pos).map(|t| t == "*").unwrap_or(false) {
+        *pos += 1;
+        let rhs = parse_unary(tokens, pos)?;
+        lhs = Expr::Mul(Box::new(lhs), Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn parse_unary(tokens: &[String], pos: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*pos).ok_or_else(|| String::from("unexpected end of expression"))?;
+    *pos += 1;
+    match token.as_str() {
+        "-" => Ok(Expr::Neg(Box::new(parse_unary(tokens, pos)?))),
+        "+" => parse_unary(tokens, pos),
+        "(" => {
+            let inner = parse_sum(tokens, pos)?;
+            match tokens.get(*pos) {
+                Some(t) if t == ")" => {
+                    *pos += 1;
+                    Ok(inner)
+                },
+                _ => Err(String::from("missing )"))
+            }
+        },
+        t if is_name(t) => Ok(Expr::Name(String::from(t))),
+        t => t.parse::<i64>().map(Expr::Num).map_err(|_| format!("invalid number {:?}", t))
+    }
+}
+
+fn eval(expr: &Expr, names: &HashMap<String, i64>) -> Result<i64, String> {
+    let overflow = || String::from("overflow in constant expression");
+    match expr {
+        Expr::Num(n) => Ok(*n),
+        Expr::Name(name) => names.get(name).cloned().ok_or_else(|| format!("undefined name {}", name)),
+        Expr::Neg(e) => eval(e, names)?.checked_neg().ok_or_else(overflow),
+        Expr::Add(l, r) => eval(l, names)?.checked_add(eval(r, names)?).ok_or_else(overflow),
+        Expr::Sub(l, r) => eval(l, names)?.checked_sub(eval(r, names)?).ok_or_else(overflow),
+        Expr::Mul(l, r) => eval(l, names)?.checked_mul(eval(r, names)?).ok_or_else(overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::disasm::disassemble;
+
+    #[test]
+    fn instructions() {
+        let src = "
+            ; sum two inputs
+            input [a]
+            input [b]
+            add [a], [b], rb+2
+            output rb + 2
+            halt
+        a:  data 0
+        b:  data 0
+        ";
+        assert_eq!(assemble(src).unwrap(), vec![3,11,3,12,20001,11,12,2,204,2,99,0,0]);
+    }
+
+    #[test]
+    fn labels_and_constants() {
+        let src = "
+            size = end - start
+            jnz #1, #start
+        start:
+            data 1, 2, 3
+        end: data size * 2, -(size + 1), rb
+        ";
+        assert!(assemble(src).is_err());
+
+        let src = "
+            size = end - start
+            jnz #1, #start
+        start:
+            data 1, 2, 3
+        end: data size * 2, -(size + 1)
+        ";
+        assert_eq!(assemble(src).unwrap(), vec![1105,1,3,1,2,3,6,-4]);
+        assert_eq!(to_text(&assemble(src).unwrap()), "1105,1,3,1,2,3,6,-4");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(assemble("add [1], #2").unwrap_err(), AsmError {line: 1, msg: String::from("add takes 3 operands, got 2")});
+        assert_eq!(assemble("\nfoo #1").unwrap_err().line, 2);
+        assert_eq!(assemble("output 5").unwrap_err().line, 1);
+        assert_eq!(assemble("x: halt\nx: halt").unwrap_err().msg, "duplicate label x");
+        assert_eq!(assemble("jz #0, #nowhere").unwrap_err().msg, "undefined name nowhere");
+    }
+
+    #[test]
+    fn round_trip() {
+        let progs: Vec<Vec<i64>> = vec![
+            vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99],
+            vec![3,9,1005,9,7,104,0,104,1,0],
+            vec![3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9],
+            vec![1105,1,4,1234,104,7,99,30,40],
+        ];
+        for prog in progs {
+            let text = disassemble(&prog).to_string();
+            assert_eq!(assemble(&text).unwrap(), prog, "{}", text);
+        }
+    }
+}
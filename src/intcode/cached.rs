@@ -0,0 +1,342 @@
+use super::{IntCodePC, IntcodeError, Memory, Opcode, ParamMode, Word, INSTRUCTIONS};
+
+// code beyond this address is always interpreted, so that a jump into
+// sparse memory does not size the cache after it
+const MAX_CACHED: usize = 1 << 20;
+
+// an instruction with its modes taken apart once; input, output and halt
+// are left to the interpreter, as are encodings with more mode digits than
+// parameters, which leave modes behind in the machine
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Decoded {
+    opcode: u8,
+    modes: [ParamMode; 3]
+}
+
+fn decode<W: Word>(ins: &W) -> Option<Decoded> {
+    let ins = ins.to_i64().filter(|&ins| ins >= 0)?;
+    let opcode = ins % 100;
+    let params = match INSTRUCTIONS.iter().find(|(code, _, _)| *code == opcode) {
+        Some(&(1, _, n)) | Some(&(2, _, n)) | Some(&(5 ..= 9, _, n)) => n,
+        _ => return None
+    };
+
+    let mut modes = [ParamMode::Position; 3];
+    let mut digits = ins / 100;
+    for mode in modes.iter_mut().take(params) {
+        *mode = match digits % 10 {
+            0 => ParamMode::Position,
+            1 => ParamMode::Immediate,
+            2 => ParamMode::Relative,
+            _ => return None
+        };
+        digits /= 10;
+    }
+    if digits != 0 {
+        return None;
+    }
+    Some(Decoded {opcode: opcode as u8, modes})
+}
+
+// decoded instructions by address; any write to an address drops what was
+// decoded there, operands are always fetched from memory
+pub struct DecodeCache {
+    entries: Vec<Option<Decoded>>,
+    hits: u64,
+    misses: u64,
+    invalidations: u64
+}
+
+impl DecodeCache {
+
+    pub fn new() -> DecodeCache {
+        DecodeCache {entries: Vec::new(), hits: 0, misses: 0, invalidations: 0}
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn invalidate(&mut self, pos: usize) {
+        if let Some(entry @ Some(_)) = self.entries.get_mut(pos) {
+            *entry = None;
+            self.invalidations += 1;
+        }
+    }
+
+    fn lookup<W: Word>(&mut self, pc: usize, memory: &Memory<W>) -> Option<Decoded> {
+        if let Some(Some(ins)) = self.entries.get(pc) {
+            self.hits += 1;
+            return Some(*ins);
+        }
+        if pc >= MAX_CACHED {
+            return None;
+        }
+
+        let ins = decode(&memory.get(pc))?;
+        self.misses += 1;
+        if pc >= self.entries.len() {
+            self.entries.resize(pc + 1, None);
+        }
+        self.entries[pc] = Some(ins);
+        Some(ins)
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        DecodeCache::new()
+    }
+}
+
+impl<'i, 'o, W: Word> IntCodePC<'i, 'o, W> {
+
+    // exec() for a machine with a cache, with the same effects on the machine
+    pub(super) fn exec_cached(&mut self) -> super::Result<Opcode<W>, W> {
+        let pc = self.pc;
+        let ins = match self.cache.as_mut().unwrap().lookup(pc, &self.memory) {
+            Some(ins) => ins,
+            None => return self.exec()
+        };
+
+        self.ins_pc = pc;
+        self.ins = self.memory.get(pc);
+        self.pc = pc + 1;
+        self.modes.clear();
+        let res = self.exec_decoded(ins);
+        if let Err(e) = &res {
+            self.leave_modes(e);
+        }
+        res
+    }
+
+    // a failed instruction leaves the modes of the parameters the interpreter
+    // did not get to in the machine; overflows fail before the write takes
+    // its mode, bad addresses after the failing parameter took its own
+    fn leave_modes(&mut self, e: &IntcodeError<W>) {
+        let done = self.pc - self.ins_pc - 1;
+        let taken = match e {
+            IntcodeError::Overflow {..} => done,
+            _ => done + 1
+        };
+        let mut digits = self.ins.to_i64().unwrap() / 100 / 10i64.pow(taken as u32);
+        while digits > 0 {
+            self.modes.push_back(match digits % 10 {
+                0 => ParamMode::Position,
+                1 => ParamMode::Immediate,
+                _ => ParamMode::Relative
+            });
+            digits /= 10;
+        }
+    }
+
+    fn exec_decoded(&mut self, ins: Decoded) -> super::Result<Opcode<W>, W> {
+        let [a, b, c] = ins.modes;
+        match ins.opcode {
+            1 => {
+                let lhs = self.read_as(a)?;
+                let rhs = self.read_as(b)?;
+                match lhs.checked_add(&rhs) {
+                    Some(sum) => self.write_as(c, sum)?,
+                    None => return Err(self.overflow())
+                }
+                Ok(Opcode::Add)
+            },
+            2 => {
+                let lhs = self.read_as(a)?;
+                let rhs = self.read_as(b)?;
+                match lhs.checked_mul(&rhs) {
+                    Some(prod) => self.write_as(c, prod)?,
+                    None => return Err(self.overflow())
+                }
+                Ok(Opcode::Mul)
+            },
+            5 | 6 => {
+                let cond = self.read_as(a)?;
+                let pos = self.read_as(b)?;
+                if cond.is_zero() == (ins.opcode == 6) {
+                    self.pc = self.address(&pos)?;
+                }
+                Ok(if ins.opcode == 5 { Opcode::Jnz } else { Opcode::Jz })
+            },
+            7 => {
+                let lhs = self.read_as(a)?;
+                let rhs = self.read_as(b)?;
+                self.write_as(c, if lhs < rhs { W::one() } else { W::zero() })?;
+                Ok(Opcode::Le)
+            },
+            8 => {
+                let lhs = self.read_as(a)?;
+                let rhs = self.read_as(b)?;
+                self.write_as(c, if lhs == rhs { W::one() } else { W::zero() })?;
+                Ok(Opcode::Eq)
+            },
+            _ => {
+                let offset = self.read_as(a)?;
+                match self.rb.checked_add(&offset) {
+                    Some(rb) => self.rb = rb,
+                    None => return Err(self.overflow())
+                }
+                Ok(Opcode::Arb)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::{Access, Hit};
+
+    // runs `prog` on an interpreter and on a cached machine side by side and
+    // checks that every step leaves both in the same state
+    fn differ(prog: Vec<i64>, inputs: &[i64], steps: usize) -> IntCodePC<'static, 'static> {
+        let mut plain = IntCodePC::new_detached(prog.clone());
+        let mut cached = IntCodePC::new_detached(prog);
+        cached.enable_cache();
+        for &val in inputs {
+            plain.feed(val);
+            cached.feed(val);
+        }
+
+        for _ in 0 .. steps {
+            let expected = format!("{:?}", plain.step_yielding());
+            let actual = format!("{:?}", cached.step_yielding());
+            assert_eq!(actual, expected);
+            assert_eq!((cached.pc, &cached.rb, cached.ins_pc, &cached.ins), (plain.pc, &plain.rb, plain.ins_pc, &plain.ins));
+            assert_eq!(cached.modes, plain.modes);
+            assert_eq!((cached.memory.len(), cached.memory.chunks()), (plain.memory.len(), plain.memory.chunks()));
+            if expected.starts_with("Err") || expected.contains("Halt") {
+                break;
+            }
+        }
+        cached
+    }
+
+    #[test]
+    fn decodes() {
+        let add = decode(&21101i64).unwrap();
+        assert_eq!((add.opcode, add.modes), (1, [ParamMode::Immediate, ParamMode::Immediate, ParamMode::Relative]));
+        assert_eq!(decode(&1205i64).unwrap().modes[.. 2], [ParamMode::Relative, ParamMode::Immediate]);
+        assert_eq!(decode(&3i64), None);
+        assert_eq!(decode(&99i64), None);
+        assert_eq!(decode(&301i64), None);
+        assert_eq!(decode(&11105i64), None);
+        assert_eq!(decode(&-1i64), None);
+    }
+
+    #[test]
+    fn same_as_interpreter() {
+        let quine = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        differ(quine, &[], 1000);
+
+        // below, at or above 8
+        let cmp = vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        for val in 7 ..= 9 {
+            differ(cmp.clone(), &[val], 1000);
+        }
+
+        differ(vec![109,7,22201,0,1,2,99,3,4,0], &[], 10);
+        differ(vec![1101,7,0,1000000000000,4,1000000000000,99], &[], 10);
+        differ(vec![1105,1,2000000,0], &[], 10);
+
+        // errors come out the same from either engine
+        differ(vec![1101,1,1,5,42,0], &[], 10);
+        differ(vec![1,-1,0,0,99], &[], 10);
+        differ(vec![109,-5,1201,1,0,0,99], &[], 10);
+        differ(vec![1102,i64::MAX,2,0,99], &[], 10);
+        differ(vec![301,0,0,0,99], &[], 10);
+        differ(vec![11101,1,2,3,99], &[], 10);
+    }
+
+    #[test]
+    fn self_modifying() {
+        // the add at 0 is cached on the first pass, then turned into a mul
+        //
+        //      0: add [20], [21], [22]
+        //      4: add [23], #-1, [23]
+        //      8: add #0, #2, [0]
+        //     12: jnz [23], #0
+        let prog = vec![1,20,21,22,1001,23,-1,23,1101,0,2,0,1005,23,0,99,0,0,0,0,6,7,0,2];
+        let pc = differ(prog, &[], 100);
+        assert_eq!(pc.memory().get(22), 42);
+        let cache = pc.cache().unwrap();
+        assert_eq!(cache.invalidations(), 2);
+        assert_eq!(cache.misses(), 5);
+        assert_eq!(cache.hits(), 3);
+    }
+
+    #[test]
+    fn external_writes() {
+        let mut pc = IntCodePC::new_detached(vec![1101,1,1,0,99]);
+        pc.enable_cache();
+        assert_eq!(pc.run().unwrap(), 2);
+        pc.reset(vec![1102,3,3,0,99]);
+        assert_eq!(pc.run().unwrap(), 9);
+
+        // a jump back to itself is cached, then rewritten from outside
+        pc.reset(vec![1105,1,0,0,99]);
+        assert_eq!(pc.step().unwrap(), Opcode::Jnz);
+        for (addr, val) in [1101, 2, 3, 9].iter().enumerate() {
+            pc.memory_mut().set(addr, *val);
+        }
+        assert_eq!(pc.run().unwrap(), 1101);
+        assert_eq!(pc.memory().get(9), 5);
+    }
+
+    #[test]
+    fn hooks() {
+        // watchpoints still fire, and the journal runs on the interpreter
+        let mut pc = IntCodePC::new_detached(vec![1001,9,1,9,1001,9,1,9,99,5]);
+        pc.enable_cache();
+        pc.watch(9, Access::Write);
+        pc.run().unwrap();
+        assert_eq!(pc.take_hits()[1], Hit {pc: 4, addr: 9, access: Access::Write, old: 6, new: 7});
+
+        pc.reset(vec![1001,9,1,9,1001,9,1,9,99,5]);
+        pc.enable_journal(10);
+        pc.run().unwrap();
+        assert!(pc.run_back_to(0));
+        assert_eq!(pc.memory().get(9), 5);
+        assert_eq!(pc.run().unwrap(), 1001);
+        assert_eq!(pc.memory().get(9), 7);
+    }
+
+    #[test]
+    fn random_programs() {
+        // small programs of mostly valid instructions over a few cells, so
+        // that they jump around and overwrite their own code
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as i64
+        };
+
+        for _ in 0 .. 500 {
+            let mut prog = Vec::new();
+            while prog.len() < 48 {
+                let opcode = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99][next(10) as usize];
+                let modes = next(3) * 100 + next(3) * 1000 + next(2) * 20000;
+                prog.push(opcode + modes);
+                for _ in 0 .. 3 {
+                    prog.push(next(56) - 4);
+                }
+            }
+            let inputs: Vec<i64> = (0 .. 4).map(|_| next(100) - 50).collect();
+            differ(prog, &inputs, 300);
+        }
+    }
+}
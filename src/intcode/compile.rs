@@ -0,0 +1,475 @@
+use super::{IntCodePC, ParamMode, Result, Word};
+use super::disasm::{decode, Instruction, Operand};
+use std::fmt::Write;
+
+// Both translations bake the words of every instruction in the image into
+// the compiled code, except for `live` ones the caller patches between runs,
+// which are read from memory each time. A write to a baked word marks the
+// instructions it belongs to stale, and reaching a stale instruction, one
+// that was not translated or one that would fail hands the rest of the run
+// to the interpreter. Input and output are compiled as single interpreter
+// steps, so they go through the machine's own I/O. A machine with hooks, one
+// that is traced, journaled, profiled, watched, limited or has devices
+// attached, is simply interpreted.
+
+// what an instruction does to the machine, None if it has to be left to the
+// interpreter; otherwise Some with the address it wrote, if any
+type Exec<W> = Box<dyn Fn(&mut IntCodePC<'_, '_, W>) -> Option<Option<usize>>>;
+type Load<W> = Box<dyn Fn(&IntCodePC<'_, '_, W>) -> Option<W>>;
+type Target<W> = Box<dyn Fn(&IntCodePC<'_, '_, W>) -> Option<usize>>;
+
+enum Node<W: Word> {
+    Exec(W, Exec<W>),
+    // an I/O instruction, with where it stores its input
+    Step(Option<Target<W>>)
+}
+
+// the instruction decoded at every address where there is one that is not
+// a halt and does not start on a live word, and for each address the
+// instructions baking the word there
+fn layout<W: Word>(image: &[W], live: &[usize]) -> (Vec<Option<Instruction<W>>>, Vec<Vec<usize>>) {
+    let mut code = Vec::new();
+    let mut owners = vec![Vec::new(); image.len()];
+    for addr in 0 .. image.len() {
+        let ins = decode(image, addr).filter(|ins| ins.opcode != 99 && !live.contains(&addr));
+        if let Some(ins) = &ins {
+            for (pos, owner) in owners.iter_mut().enumerate().skip(addr).take(ins.width()) {
+                if !live.contains(&pos) {
+                    owner.push(addr);
+                }
+            }
+        }
+        code.push(ins);
+    }
+    (code, owners)
+}
+
+fn relative<W: Word>(vm: &IntCodePC<'_, '_, W>, offset: &W) -> Option<usize> {
+    vm.rb.checked_add(offset)?.to_address()
+}
+
+// a program translated to closures, for running it many times over
+pub struct Compiled<W: Word = i64> {
+    code: Vec<Option<Node<W>>>,
+    owners: Vec<Vec<usize>>,
+    image: Vec<W>
+}
+
+impl<W: Word> Compiled<W> {
+
+    pub fn new(image: &[W], live: &[usize]) -> Compiled<W> {
+        let (code, owners) = layout(image, live);
+        let code = code.into_iter().map(|ins| ins.map(|ins| Compiled::node(&ins, live))).collect();
+        Compiled {code, owners, image: image.to_vec()}
+    }
+
+    fn node(ins: &Instruction<W>, live: &[usize]) -> Node<W> {
+        let next = ins.addr + ins.width();
+        let load = |n: usize| Compiled::load(&ins.operands[n], ins.addr + 1 + n, live.contains(&(ins.addr + 1 + n)));
+        let target = |n: usize| Compiled::target(&ins.operands[n], ins.addr + 1 + n, live.contains(&(ins.addr + 1 + n)));
+
+        let exec: Exec<W> = match ins.opcode {
+            1 | 2 | 7 | 8 => {
+                let f: fn(&W, &W) -> Option<W> = match ins.opcode {
+                    1 => |a, b| a.checked_add(b),
+                    2 => |a, b| a.checked_mul(b),
+                    7 => |a, b| Some(if a < b { W::one() } else { W::zero() }),
+                    _ => |a, b| Some(if a == b { W::one() } else { W::zero() })
+                };
+                let (a, b, c) = (load(0), load(1), target(2));
+                Box::new(move |vm| {
+                    let val = f(&a(vm)?, &b(vm)?)?;
+                    let pos = c(vm)?;
+                    vm.store(pos, val);
+                    vm.pc = next;
+                    Some(Some(pos))
+                })
+            },
+            5 | 6 => {
+                let jz = ins.opcode == 6;
+                let (a, b) = (load(0), load(1));
+                Box::new(move |vm| {
+                    let cond = a(vm)?;
+                    let pos = b(vm)?;
+                    vm.pc = if cond.is_zero() == jz { pos.to_address()? } else { next };
+                    Some(None)
+                })
+            },
+            9 => {
+                let a = load(0);
+                Box::new(move |vm| {
+                    vm.rb = vm.rb.checked_add(&a(vm)?)?;
+                    vm.pc = next;
+                    Some(None)
+                })
+            },
+            3 => return Node::Step(Some(target(0))),
+            _ => return Node::Step(None)
+        };
+        Node::Exec(W::from_i64(ins.encode()), exec)
+    }
+
+    fn load(op: &Operand<W>, at: usize, live: bool) -> Load<W> {
+        let val = op.value.clone();
+        match (op.mode, live) {
+            (ParamMode::Position, false) => match val.to_address() {
+                Some(pos) => Box::new(move |vm| Some(vm.memory.get(pos))),
+                None => Box::new(|_| None)
+            },
+            (ParamMode::Immediate, false) => Box::new(move |_| Some(val.clone())),
+            (ParamMode::Relative, false) => Box::new(move |vm| relative(vm, &val).map(|pos| vm.memory.get(pos))),
+            (ParamMode::Position, true) => Box::new(move |vm| vm.memory.get(at).to_address().map(|pos| vm.memory.get(pos))),
+            (ParamMode::Immediate, true) => Box::new(move |vm| Some(vm.memory.get(at))),
+            (ParamMode::Relative, true) => Box::new(move |vm| relative(vm, &vm.memory.get(at)).map(|pos| vm.memory.get(pos)))
+        }
+    }
+
+    fn target(op: &Operand<W>, at: usize, live: bool) -> Target<W> {
+        let val = op.value.clone();
+        match (op.mode, live) {
+            (ParamMode::Relative, false) => Box::new(move |vm| relative(vm, &val)),
+            (ParamMode::Relative, true) => Box::new(move |vm| relative(vm, &vm.memory.get(at))),
+            (_, false) => {
+                let pos = val.to_address();
+                Box::new(move |_| pos)
+            },
+            (_, true) => Box::new(move |vm| vm.memory.get(at).to_address())
+        }
+    }
+
+    fn mark(&self, stale: &mut [bool], pos: usize) {
+        if let Some(owners) = self.owners.get(pos) {
+            for &start in owners.iter() {
+                stale[start] = true;
+            }
+        }
+    }
+
+    // runs the program from where `vm` is, like vm.run()
+    pub fn run(&self, vm: &mut IntCodePC<'_, '_, W>) -> Result<W, W> {
+        if vm.has_hooks() {
+            return vm.run();
+        }
+
+        let mut stale = vec![false; self.code.len()];
+        for (pos, word) in self.image.iter().enumerate() {
+            if !self.owners[pos].is_empty() && vm.memory.get(pos) != *word {
+                self.mark(&mut stale, pos);
+            }
+        }
+
+        vm.modes.clear();
+        loop {
+            let pc = vm.pc;
+            let node = match self.code.get(pc) {
+                Some(Some(node)) if !stale[pc] => node,
+                _ => return vm.run()
+            };
+
+            let wrote = match node {
+                Node::Exec(ins, exec) => {
+                    vm.ins_pc = pc;
+                    vm.ins = ins.clone();
+                    match exec(vm) {
+                        Some(wrote) => wrote,
+                        None => return vm.run()
+                    }
+                },
+                Node::Step(target) => {
+                    let pos = match target.as_ref().map(|t| t(vm)) {
+                        Some(None) => return vm.run(),
+                        Some(pos) => pos,
+                        None => None
+                    };
+                    vm.step()?;
+                    pos
+                }
+            };
+            if let Some(pos) = wrote {
+                self.mark(&mut stale, pos);
+            }
+        }
+    }
+}
+
+// the same translation as Rust source for a function `name` that takes the
+// machine to run the program on; the code it generates uses let-else
+pub fn to_rust(image: &[i64], name: &str, live: &[usize]) -> String {
+    let (code, owners) = layout(image, live);
+    let n = image.len();
+    let mut out = String::new();
+
+    let baked: Vec<String> = owners.iter().enumerate().filter(|(_, o)| !o.is_empty()).map(|(pos, _)| format!("({}, {})", pos, image[pos])).collect();
+    let owners: Vec<String> = owners.iter().map(|o| format!("&{:?}", o)).collect();
+
+    writeln!(out, "// Generated by intcodepc compile. Runs an Intcode program on `vm`, whose").unwrap();
+    writeln!(out, "// memory has to start out as the image it was compiled from except for").unwrap();
+    writeln!(out, "// the words {:?}; whatever the translation does not cover,", live).unwrap();
+    writeln!(out, "// including instructions the program overwrote and machines with hooks,").unwrap();
+    writeln!(out, "// is left to the interpreter.").unwrap();
+    writeln!(out, "#[allow(dead_code, unused_labels, unused_mut, unused_variables, clippy::all)]").unwrap();
+    writeln!(out, "pub fn {}(vm: &mut aoc2019::intcode::IntCodePC<'_, '_>) -> aoc2019::intcode::Result<i64> {{", name).unwrap();
+    writeln!(out, "    const BAKED: [(usize, i64); {}] = [{}];", baked.len(), baked.join(", ")).unwrap();
+    writeln!(out, "    const OWNERS: [&[usize]; {}] = [{}];", n, owners.join(", ")).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn addr(val: i64) -> Option<usize> {{").unwrap();
+    writeln!(out, "        if val >= 0 {{ Some(val as usize) }} else {{ None }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn rel(rb: i64, off: i64) -> Option<usize> {{").unwrap();
+    writeln!(out, "        rb.checked_add(off).and_then(addr)").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn mark(stale: &mut [bool; {}], pos: usize) {{", n).unwrap();
+    writeln!(out, "        if let Some(owners) = OWNERS.get(pos) {{").unwrap();
+    writeln!(out, "            for &start in owners.iter() {{").unwrap();
+    writeln!(out, "                stale[start] = true;").unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    if vm.has_hooks() {{").unwrap();
+    writeln!(out, "        return vm.run();").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    let mut stale = [false; {}];", n).unwrap();
+    writeln!(out, "    for &(pos, word) in BAKED.iter() {{").unwrap();
+    writeln!(out, "        if vm.memory().get(pos) != word {{").unwrap();
+    writeln!(out, "            mark(&mut stale, pos);").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    let mut pc = vm.pc();").unwrap();
+    writeln!(out, "    let mut rb = *vm.rb();").unwrap();
+    writeln!(out, "    'run: loop {{").unwrap();
+    writeln!(out, "        match pc {{").unwrap();
+    for ins in code.iter().flatten() {
+        writeln!(out, "            {} if !stale[{}] => 'ins: {{", ins.addr, ins.addr).unwrap();
+        for line in rust_body(ins, live) {
+            writeln!(out, "                {}", line).unwrap();
+        }
+        writeln!(out, "            }},").unwrap();
+    }
+    writeln!(out, "            _ => {{}}").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "        vm.set_registers(pc, rb);").unwrap();
+    writeln!(out, "        return vm.run();").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+// the statements of one match arm; `break 'ins` leaves the instruction to
+// the interpreter before it changed anything
+fn rust_body(ins: &Instruction<i64>, live: &[usize]) -> Vec<String> {
+    let next = ins.addr + ins.width();
+    let word = |n: usize| {
+        let at = ins.addr + 1 + n;
+        if live.contains(&at) { format!("m.get({})", at) } else { format!("{}i64", ins.operands[n].value) }
+    };
+    let load = |n: usize| match ins.operands[n].mode {
+        ParamMode::Immediate => format!("Some({})", word(n)),
+        ParamMode::Position => format!("addr({}).map(|p| m.get(p))", word(n)),
+        ParamMode::Relative => format!("rel(rb, {}).map(|p| m.get(p))", word(n))
+    };
+    let target = |n: usize| match ins.operands[n].mode {
+        ParamMode::Relative => format!("rel(rb, {})", word(n)),
+        _ => format!("addr({})", word(n))
+    };
+    let bail = "else { break 'ins };";
+
+    let mut body = vec![String::from("let m = vm.memory();")];
+    match ins.opcode {
+        1 | 2 | 7 | 8 => {
+            body.push(format!("let Some(a) = {} {}", load(0), bail));
+            body.push(format!("let Some(b) = {} {}", load(1), bail));
+            body.push(match ins.opcode {
+                1 => format!("let Some(val) = a.checked_add(b) {}", bail),
+                2 => format!("let Some(val) = a.checked_mul(b) {}", bail),
+                7 => String::from("let val = (a < b) as i64;"),
+                _ => String::from("let val = (a == b) as i64;")
+            });
+            body.push(format!("let Some(pos) = {} {}", target(2), bail));
+            body.push(String::from("vm.store_word(pos, val);"));
+            body.push(String::from("mark(&mut stale, pos);"));
+            body.push(format!("pc = {};", next));
+        },
+        5 | 6 => {
+            body.push(format!("let Some(a) = {} {}", load(0), bail));
+            body.push(format!("let Some(b) = {} {}", load(1), bail));
+            body.push(format!("if a {} 0 {{", if ins.opcode == 5 { "!=" } else { "==" }));
+            body.push(format!("    let Some(to) = addr(b) {}", bail));
+            body.push(String::from("    pc = to;"));
+            body.push(String::from("} else {"));
+            body.push(format!("    pc = {};", next));
+            body.push(String::from("}"));
+        },
+        9 => {
+            body.push(format!("let Some(a) = {} {}", load(0), bail));
+            body.push(format!("let Some(base) = rb.checked_add(a) {}", bail));
+            body.push(String::from("rb = base;"));
+            body.push(format!("pc = {};", next));
+        },
+        3 => {
+            body.push(format!("let Some(pos) = {} {}", target(0), bail));
+            body.push(String::from("vm.set_registers(pc, rb);"));
+            body.push(String::from("vm.step()?;"));
+            body.push(String::from("mark(&mut stale, pos);"));
+            body.push(String::from("pc = vm.pc();"));
+        },
+        _ => {
+            body[0] = String::from("vm.set_registers(pc, rb);");
+            body.push(String::from("vm.step()?;"));
+            body.push(String::from("pc = vm.pc();"));
+        }
+    }
+    body.push(String::from("continue 'run;"));
+    body
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::Opcode;
+
+    // compiles `prog` and checks that running it matches the interpreter,
+    // result, registers and memory
+    fn same(prog: Vec<i64>, live: &[usize], inputs: &[i64]) -> IntCodePC<'static, 'static> {
+        let mut plain = IntCodePC::new_detached(prog.clone());
+        let mut compiled = IntCodePC::new_detached(prog.clone());
+        for &val in inputs {
+            plain.feed(val);
+            compiled.feed(val);
+        }
+
+        let expected = format!("{:?}", plain.run());
+        assert_eq!(format!("{:?}", Compiled::new(&prog, live).run(&mut compiled)), expected);
+        assert_eq!((compiled.pc, compiled.rb, compiled.ins_pc, compiled.ins), (plain.pc, plain.rb, plain.ins_pc, plain.ins));
+        assert_eq!((compiled.memory.len(), compiled.memory.chunks()), (plain.memory.len(), plain.memory.chunks()));
+        assert_eq!(compiled.queue, plain.queue);
+        compiled
+    }
+
+    #[test]
+    fn same_as_interpreter() {
+        same(vec![1,9,10,3,2,3,11,0,99,30,40,50], &[], &[]);
+        same(vec![1,1,1,4,99,5,6,0,99], &[], &[]);
+        same(vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99], &[], &[]);
+        same(vec![1102,34915192,34915192,7,4,7,99,0], &[], &[]);
+        same(vec![109,7,22201,0,1,2,99,3,4,0], &[], &[]);
+        same(vec![1101,7,0,1000000000000,4,1000000000000,99], &[], &[]);
+        same(vec![3,11,1002,11,2,11,4,11,1105,1,0,0], &[], &[1, 2, 3]);
+        same(vec![203,5,99], &[], &[4]);
+
+        let cmp = vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        for val in 7 ..= 9 {
+            same(cmp.clone(), &[], &[val]);
+        }
+
+        // failures are left to the interpreter
+        same(vec![1101,1,1,5,42,0], &[], &[]);
+        same(vec![1,-1,0,0,99], &[], &[]);
+        same(vec![109,-5,1201,1,0,0,99], &[], &[]);
+        same(vec![1102,i64::MAX,2,0,99], &[], &[]);
+        same(vec![3,0,99], &[], &[]);
+        same(vec![1105,1,-3], &[], &[]);
+    }
+
+    #[test]
+    fn self_modifying() {
+        // the add at 0 turns itself into a mul on the second pass
+        let prog = vec![1,20,21,22,1001,23,-1,23,1101,0,2,0,1005,23,0,99,0,0,0,0,6,7,0,2];
+        assert_eq!(same(prog, &[], &[]).memory().get(22), 42);
+
+        // [4] = 99 before reaching it
+        same(vec![1002,4,3,4,33], &[], &[]);
+
+        // input written over the next instruction's operand
+        same(vec![3,3,104,0,99], &[], &[77]);
+    }
+
+    #[test]
+    fn live_words() {
+        // day 2: noun and verb are operands of the first instruction
+        let prog = vec![1,0,0,3,1,1,2,3,1,3,4,3,1,5,0,3,2,1,10,19,2,19,9,23,99];
+        let compiled = Compiled::new(&prog, &[1, 2]);
+        let mut pc = IntCodePC::new_detached(prog.clone());
+        let start = pc.snapshot();
+        for (noun, verb) in [(0, 0), (12, 2), (5, 7), (1, 19)].iter() {
+            pc.restore(&start);
+            pc.init(*noun, *verb);
+            let mut plain = IntCodePC::new_detached(prog.clone());
+            plain.init(*noun, *verb);
+            assert_eq!(compiled.run(&mut pc).unwrap(), plain.run().unwrap());
+            assert_eq!(pc.memory().to_vec(), plain.memory().to_vec());
+        }
+
+        // a patched word that was baked in is noticed
+        pc.restore(&start);
+        pc.memory_mut().set(4, 2);
+        let mut plain = IntCodePC::new_detached(prog.clone());
+        plain.memory_mut().set(4, 2);
+        assert_eq!(compiled.run(&mut pc).unwrap(), plain.run().unwrap());
+    }
+
+    #[test]
+    fn random_programs() {
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as i64
+        };
+
+        for _ in 0 .. 500 {
+            // jumps only go forward so that every program ends
+            let mut prog = Vec::new();
+            while prog.len() < 48 {
+                let opcode = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99][next(10) as usize];
+                let modes = next(3) * 100 + next(3) * 1000 + next(2) * 20000;
+                prog.push(opcode + modes);
+                let here = prog.len() as i64;
+                for _ in 0 .. 3 {
+                    prog.push(if opcode == 5 || opcode == 6 { here + next(56) } else { next(56) - 4 });
+                }
+            }
+            let inputs: Vec<i64> = (0 .. 4).map(|_| next(100) - 50).collect();
+            let mut plain = IntCodePC::new_detached(prog.clone());
+            let mut compiled = IntCodePC::new_detached(prog.clone());
+            for &val in inputs.iter() {
+                plain.feed(val);
+                compiled.feed(val);
+            }
+            let mut steps = 0;
+            let looping = loop {
+                match plain.step() {
+                    Ok(Opcode::Halt(_)) | Err(_) => break false,
+                    _ if steps == 1000 => break true,
+                    _ => steps += 1
+                }
+            };
+            if !looping {
+                same(prog, &[], &inputs);
+            }
+        }
+    }
+
+    #[test]
+    fn rust_source() {
+        let src = to_rust(&[1101,1,2,5,99,0], "run", &[2]);
+        assert!(src.contains("const BAKED: [(usize, i64); 5] = [(0, 1101), (1, 1), (3, 5), (4, 99), (5, 0)];"), "{}", src);
+        assert!(src.contains("const OWNERS: [&[usize]; 6] = [&[0], &[0, 1], &[], &[0, 1, 3], &[1, 3], &[3]];"), "{}", src);
+        assert!(src.contains(concat!(
+            "            0 if !stale[0] => 'ins: {\n",
+            "                let m = vm.memory();\n",
+            "                let Some(a) = Some(1i64) else { break 'ins };\n",
+            "                let Some(b) = Some(m.get(2)) else { break 'ins };\n",
+            "                let Some(val) = a.checked_add(b) else { break 'ins };\n",
+            "                let Some(pos) = addr(5i64) else { break 'ins };\n",
+            "                vm.store_word(pos, val);\n",
+            "                mark(&mut stale, pos);\n",
+            "                pc = 4;\n",
+            "                continue 'run;\n",
+            "            },\n")), "{}", src);
+    }
+}
@@ -0,0 +1,700 @@
+use super::{Access, IntCodePC, Opcode, Snapshot, Word, INSTRUCTIONS};
+use super::disasm::{decode_in, disassemble};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+const LIST_BEFORE: usize = 3;
+const LIST_LINES: usize = 10;
+const MEM_PER_LINE: usize = 8;
+const JOURNAL_LEN: usize = 100_000;
+
+const HELP: &str = "\
+commands:
+  step [n]                  execute n instructions (default 1)
+  continue                  run until a breakpoint, watchpoint, halt, error or missing input
+  back [n]                  undo n instructions (default 1)
+  back to <addr>            undo instructions until the pc is addr
+  back until <addr>         undo instructions until just before addr was last written
+  journal <len>             how many instructions back can undo (default 100000)
+  break <addr|opcode>       break at an address, or before any instruction with that mnemonic
+  break <addr|opcode> if <cond>
+                            only break there while the condition holds
+  break when <cond>         break as soon as the condition becomes true
+  unbreak <addr|opcode>     remove a breakpoint
+  unbreak when <cond>
+  watch <addr>              stop after every write to an address
+  rwatch <addr>             stop after every read of an address
+  unwatch <addr>            remove both kinds of watchpoint
+  breaks                    list breakpoints and watchpoints
+  mem <addr> [count]        show memory
+  set <addr> <value>        change memory
+  regs                      show pc, relative base and queued input
+  list [addr]               disassemble around the pc or from addr
+  input <v>...              queue values for the program's input instructions
+  save <file>               write a snapshot of the machine, for intcodepc --resume
+  load <file>               restore the machine from a snapshot
+  help                      show this text
+  quit                      leave the debugger
+
+conditions compare pc, rb, [addr] or numbers with == != < <= > >=, e.g. [224] == 0";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Breakpoint {
+    Addr(usize),
+    Opcode(i64)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value<W: Word = i64> {
+    Pc,
+    Rb,
+    Mem(usize),
+    Const(W)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+// longer operators first, so that "<=" is not taken for "<"
+const CMPS: [(&str, Cmp); 6] = [
+    ("==", Cmp::Eq),
+    ("!=", Cmp::Ne),
+    ("<=", Cmp::Le),
+    (">=", Cmp::Ge),
+    ("<", Cmp::Lt),
+    (">", Cmp::Gt)
+];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Condition<W: Word = i64> {
+    pub lhs: Value<W>,
+    pub cmp: Cmp,
+    pub rhs: Value<W>
+}
+
+impl<W: Word> Value<W> {
+
+    pub fn parse(text: &str) -> Result<Value<W>, String> {
+        let text = text.trim();
+        match text {
+            "pc" => Ok(Value::Pc),
+            "rb" => Ok(Value::Rb),
+            _ => match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+                Some(addr) => addr.trim().parse().map(Value::Mem).map_err(|_| format!("invalid address {:?}", addr)),
+                None => W::parse(text).map(Value::Const).map_err(|_| format!("invalid value {:?}", text))
+            }
+        }
+    }
+
+    pub fn get(&self, pc: &IntCodePC<'_, '_, W>) -> W {
+        match self {
+            Value::Pc => W::from_i64(pc.pc() as i64),
+            Value::Rb => pc.rb().clone(),
+            Value::Mem(addr) => pc.memory().get(*addr),
+            Value::Const(val) => val.clone()
+        }
+    }
+}
+
+impl<W: Word> Condition<W> {
+
+    pub fn parse(text: &str) -> Result<Condition<W>, String> {
+        for &(op, cmp) in CMPS.iter() {
+            if let Some(at) = text.find(op) {
+                let lhs = Value::parse(&text[.. at])?;
+                let rhs = Value::parse(&text[at + op.len() ..])?;
+                return Ok(Condition {lhs, cmp, rhs});
+            }
+        }
+        Err(format!("no comparison in {:?}", text.trim()))
+    }
+
+    pub fn eval(&self, pc: &IntCodePC<'_, '_, W>) -> bool {
+        let lhs = self.lhs.get(pc);
+        let rhs = self.rhs.get(pc);
+        match self.cmp {
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Value<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Pc => write!(f, "pc"),
+            Value::Rb => write!(f, "rb"),
+            Value::Mem(addr) => write!(f, "[{}]", addr),
+            Value::Const(val) => write!(f, "{}", val)
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Condition<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let &(op, _) = CMPS.iter().find(|(_, cmp)| *cmp == self.cmp).unwrap();
+        write!(f, "{} {} {}", self.lhs, op, self.rhs)
+    }
+}
+
+// a `break when` condition, with what it and its left hand side were
+// before the last instruction, so that it fires on becoming true
+struct Trigger<W: Word> {
+    cond: Condition<W>,
+    held: bool,
+    last: W
+}
+
+// Drives a detached machine from text commands. Program input is only ever
+// taken from the queue filled by `input`, so it never mixes with commands.
+pub struct Debugger<W: Word = i64> {
+    pc: IntCodePC<'static, 'static, W>,
+    breakpoints: BTreeMap<usize, Option<Condition<W>>>,
+    opcodes: BTreeMap<i64, Option<Condition<W>>>,
+    triggers: Vec<Trigger<W>>,
+    halted: Option<W>
+}
+
+impl<W: Word> Debugger<W> {
+
+    pub fn new(program: Vec<W>) -> Debugger<W> {
+        let mut pc = IntCodePC::new_detached(program);
+        pc.enable_journal(JOURNAL_LEN);
+        Debugger {
+            pc,
+            breakpoints: BTreeMap::new(),
+            opcodes: BTreeMap::new(),
+            triggers: Vec::new(),
+            halted: None
+        }
+    }
+
+    pub fn machine(&self) -> &IntCodePC<'static, 'static, W> {
+        &self.pc
+    }
+
+    pub fn halted(&self) -> Option<&W> {
+        self.halted.as_ref()
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint, cond: Option<Condition<W>>) {
+        match bp {
+            Breakpoint::Addr(addr) => self.breakpoints.insert(addr, cond),
+            Breakpoint::Opcode(op) => self.opcodes.insert(op, cond)
+        };
+    }
+
+    pub fn remove_breakpoint(&mut self, bp: Breakpoint) -> bool {
+        match bp {
+            Breakpoint::Addr(addr) => self.breakpoints.remove(&addr).is_some(),
+            Breakpoint::Opcode(op) => self.opcodes.remove(&op).is_some()
+        }
+    }
+
+    pub fn add_condition(&mut self, cond: Condition<W>) {
+        let held = cond.eval(&self.pc);
+        let last = cond.lhs.get(&self.pc);
+        self.triggers.push(Trigger {cond, held, last});
+    }
+
+    pub fn remove_condition(&mut self, cond: &Condition<W>) -> bool {
+        let len = self.triggers.len();
+        self.triggers.retain(|t| t.cond != *cond);
+        self.triggers.len() != len
+    }
+
+    pub fn watch(&mut self, addr: usize, access: Access) {
+        self.pc.watch(addr, access);
+    }
+
+    pub fn unwatch(&mut self, addr: usize) -> bool {
+        let read = self.pc.unwatch(addr, Access::Read);
+        self.pc.unwatch(addr, Access::Write) || read
+    }
+
+    // runs one debugger command, false once the user asked to quit
+    pub fn execute(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => return Ok(true)
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (cmd, args.as_slice()) {
+            ("s", []) | ("step", []) => self.step(1, out)?,
+            ("s", [n]) | ("step", [n]) => match n.parse() {
+                Ok(n) => self.step(n, out)?,
+                Err(_) => writeln!(out, "invalid count {:?}", n)?
+            },
+            ("c", []) | ("continue", []) => self.step(usize::MAX, out)?,
+            ("back", []) => self.back(1, out)?,
+            ("back", [n]) => match n.parse() {
+                Ok(n) => self.back(n, out)?,
+                Err(_) => writeln!(out, "invalid count {:?}", n)?
+            },
+            ("back", [how, addr]) if *how == "to" || *how == "until" => match addr.parse() {
+                Ok(addr) => {
+                    let found = if *how == "to" { self.pc.run_back_to(addr) } else { self.pc.run_back_to_write(addr) };
+                    if !found {
+                        writeln!(out, "journal exhausted")?;
+                    }
+                    self.halted = None;
+                    self.list_at(self.pc.pc(), 1, out)?;
+                },
+                Err(_) => writeln!(out, "invalid address {:?}", addr)?
+            },
+            ("journal", [len]) => match len.parse() {
+                Ok(len) => self.pc.enable_journal(len),
+                Err(_) => writeln!(out, "invalid length {:?}", len)?
+            },
+            ("b", args) | ("break", args) if !args.is_empty() => {
+                if let Err(e) = self.set_break(args) {
+                    writeln!(out, "{}", e)?;
+                }
+            },
+            ("unbreak", ["when", cond @ ..]) => match Condition::parse(&cond.join(" ")) {
+                Ok(cond) if self.remove_condition(&cond) => {},
+                _ => writeln!(out, "no breakpoint when {:?}", cond.join(" "))?
+            },
+            ("unbreak", [bp]) => match parse_breakpoint(bp) {
+                Some(bp) if self.remove_breakpoint(bp) => {},
+                _ => writeln!(out, "no breakpoint {:?}", bp)?
+            },
+            ("watch", [addr]) | ("rwatch", [addr]) => match addr.parse() {
+                Ok(addr) if cmd == "watch" => self.watch(addr, Access::Write),
+                Ok(addr) => self.watch(addr, Access::Read),
+                Err(_) => writeln!(out, "invalid address {:?}", addr)?
+            },
+            ("unwatch", [addr]) => match addr.parse() {
+                Ok(addr) if self.unwatch(addr) => {},
+                _ => writeln!(out, "no watchpoint {:?}", addr)?
+            },
+            ("breaks", []) => self.breaks(out)?,
+            ("m", [addr]) | ("mem", [addr]) => self.mem(addr, "1", out)?,
+            ("m", [addr, count]) | ("mem", [addr, count]) => self.mem(addr, count, out)?,
+            ("set", [addr, val]) => match (addr.parse::<usize>(), W::parse(val)) {
+                (Ok(addr), Ok(val)) => self.pc.memory_mut().set(addr, val),
+                _ => writeln!(out, "usage: set <addr> <value>")?
+            },
+            ("r", []) | ("regs", []) => {
+                writeln!(out, "pc {}  rb {}  queued input {}", self.pc.pc(), self.pc.rb(), self.pc.pending_input())?
+            },
+            ("l", []) | ("list", []) => self.list(None, out)?,
+            ("l", [addr]) | ("list", [addr]) => match addr.parse() {
+                Ok(addr) => self.list(Some(addr), out)?,
+                Err(_) => writeln!(out, "invalid address {:?}", addr)?
+            },
+            ("i", vals) | ("input", vals) if !vals.is_empty() => {
+                match vals.iter().map(|v| W::parse(v)).collect::<Result<Vec<W>, String>>() {
+                    Ok(vals) => vals.into_iter().for_each(|v| self.pc.feed(v)),
+                    Err(e) => writeln!(out, "invalid input: {}", e)?
+                }
+            },
+            ("save", [path]) => {
+                if let Err(e) = self.save(path) {
+                    writeln!(out, "{}: {}", path, e)?;
+                }
+            },
+            ("load", [path]) => match self.load(path) {
+                Ok(()) => self.list_at(self.pc.pc(), 1, out)?,
+                Err(e) => writeln!(out, "{}: {}", path, e)?
+            },
+            ("h", []) | ("help", []) => writeln!(out, "{}", HELP)?,
+            ("q", []) | ("quit", []) => return Ok(false),
+            _ => writeln!(out, "unknown command {:?}, try help", line.trim())?
+        }
+        Ok(true)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.pc.snapshot().write_to(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let snap = Snapshot::read_from(&mut BufReader::new(File::open(path)?))?;
+        self.pc.restore(&snap);
+        self.halted = None;
+        Ok(())
+    }
+
+    fn set_break(&mut self, args: &[&str]) -> Result<(), String> {
+        match args {
+            ["when", cond @ ..] => self.add_condition(Condition::parse(&cond.join(" "))?),
+            [bp, rest @ ..] => {
+                let bp = parse_breakpoint(bp).ok_or_else(|| format!("invalid breakpoint {:?}", bp))?;
+                let cond = match rest {
+                    [] => None,
+                    ["if", cond @ ..] => Some(Condition::parse(&cond.join(" "))?),
+                    _ => return Err(format!("expected if <cond> after {:?}", args[0]))
+                };
+                self.add_breakpoint(bp, cond);
+            },
+            [] => {}
+        }
+        Ok(())
+    }
+
+    // executes up to n instructions, stopping early at a breakpoint (other
+    // than the one the pc starts on), watchpoint, halt, error or an input
+    // with no queue; breakpoints the last instruction lands on are reported
+    fn step(&mut self, n: usize, out: &mut dyn Write) -> io::Result<()> {
+        if let Some(val) = &self.halted {
+            return writeln!(out, "halted with {}", val);
+        }
+
+        let mut last_pc = self.pc.pc();
+        let mut count = 0;
+        loop {
+            if self.check_breaks(count > 0, last_pc, out)? || count == n {
+                break;
+            }
+            if self.pc.pending_input() == 0 && self.pc.peek_opcode() == Some(3) {
+                writeln!(out, "waiting for input at {}", self.pc.pc())?;
+                break;
+            }
+
+            last_pc = self.pc.pc();
+            match self.pc.step_yielding() {
+                Ok((Opcode::Halt(val), _)) => {
+                    writeln!(out, "halted with {}", val)?;
+                    self.halted = Some(val);
+                    return Ok(());
+                },
+                Ok((_, Some(val))) => writeln!(out, "output {}", val)?,
+                Ok(_) => {},
+                Err(e) => {
+                    writeln!(out, "error: {}", e)?;
+                    break;
+                }
+            }
+            count += 1;
+
+            let hits = self.pc.take_hits();
+            for hit in hits.iter() {
+                writeln!(out, "watch {}", hit)?;
+            }
+            if !hits.is_empty() {
+                self.check_breaks(true, last_pc, out)?;
+                break;
+            }
+        }
+        self.list_at(self.pc.pc(), 1, out)
+    }
+
+    fn back(&mut self, n: usize, out: &mut dyn Write) -> io::Result<()> {
+        for _ in 0 .. n {
+            if !self.pc.step_back() {
+                writeln!(out, "journal exhausted")?;
+                break;
+            }
+            self.halted = None;
+        }
+        self.list_at(self.pc.pc(), 1, out)
+    }
+
+    // reports the breakpoints that hold at the current pc when armed; the
+    // `break when` conditions are brought up to date either way
+    fn check_breaks(&mut self, armed: bool, last_pc: usize, out: &mut dyn Write) -> io::Result<bool> {
+        let mut stop = false;
+        for t in self.triggers.iter_mut() {
+            let held = t.cond.eval(&self.pc);
+            let now = t.cond.lhs.get(&self.pc);
+            if armed && held && !t.held {
+                writeln!(out, "break when {} hit at {}: {} {} -> {}", t.cond, last_pc, t.cond.lhs, t.last, now)?;
+                stop = true;
+            }
+            t.held = held;
+            t.last = now;
+        }
+        if !armed {
+            return Ok(false);
+        }
+
+        let pc = self.pc.pc();
+        let at_addr = self.breakpoints.get(&pc);
+        let at_opcode = self.pc.peek_opcode().and_then(|op| self.opcodes.get(&op));
+        for cond in at_addr.into_iter().chain(at_opcode) {
+            match cond {
+                None => writeln!(out, "breakpoint at {}", pc)?,
+                Some(cond) if cond.eval(&self.pc) => writeln!(out, "breakpoint at {} if {}", pc, cond)?,
+                Some(_) => continue
+            }
+            stop = true;
+            break;
+        }
+        Ok(stop)
+    }
+
+    fn breaks(&self, out: &mut dyn Write) -> io::Result<()> {
+        let locations = self.breakpoints.iter().map(|(addr, cond)| (addr.to_string(), cond));
+        let opcodes = self.opcodes.iter().map(|(op, cond)| {
+            let &(_, mnemonic, _) = INSTRUCTIONS.iter().find(|(code, _, _)| code == op).unwrap();
+            (String::from(mnemonic), cond)
+        });
+        for (at, cond) in locations.chain(opcodes) {
+            match cond {
+                Some(cond) => writeln!(out, "break {} if {}", at, cond)?,
+                None => writeln!(out, "break {}", at)?
+            }
+        }
+        for t in self.triggers.iter() {
+            writeln!(out, "break when {}", t.cond)?;
+        }
+        for addr in self.pc.watches().watched(Access::Write) {
+            writeln!(out, "watch {}", addr)?;
+        }
+        for addr in self.pc.watches().watched(Access::Read) {
+            writeln!(out, "rwatch {}", addr)?;
+        }
+        Ok(())
+    }
+
+    fn mem(&self, addr: &str, count: &str, out: &mut dyn Write) -> io::Result<()> {
+        let (start, count) = match (addr.parse::<usize>(), count.parse::<usize>()) {
+            (Ok(addr), Ok(count)) => (addr, count),
+            _ => return writeln!(out, "usage: mem <addr> [count]")
+        };
+
+        let last = match start.checked_add(count) {
+            Some(last) => last,
+            None => return writeln!(out, "range {}+{} is past the end of the address space", start, count)
+        };
+        let mut addr = start;
+        while addr < last {
+            let end = addr.saturating_add(MEM_PER_LINE).min(last);
+            let words: Vec<String> = (addr .. end).map(|a| self.pc.memory().get(a).to_string()).collect();
+            writeln!(out, "{:04}: {}", addr, words.join(" "))?;
+            addr = end;
+        }
+        Ok(())
+    }
+
+    // without an address the listing starts a few statically known
+    // instructions before the pc, so that the context leading up to it
+    // shows; only the memory allocated contiguously from 0 is disassembled
+    fn list(&self, addr: Option<usize>, out: &mut dyn Write) -> io::Result<()> {
+        let start = addr.unwrap_or_else(|| {
+            let mut image = Vec::new();
+            for (start, chunk) in self.pc.memory().chunks() {
+                if start != image.len() {
+                    break;
+                }
+                image.extend_from_slice(chunk);
+            }
+            let listing = disassemble(&image);
+            let mut start = self.pc.pc();
+            for (&addr, ins) in listing.code.range(.. start).rev().take(LIST_BEFORE) {
+                if addr + ins.width() != start {
+                    break;
+                }
+                start = addr;
+            }
+            start
+        });
+        self.list_at(start, LIST_LINES, out)
+    }
+
+    fn list_at(&self, start: usize, lines: usize, out: &mut dyn Write) -> io::Result<()> {
+        let memory = self.pc.memory();
+        let mut addr = start;
+        for _ in 0 .. lines {
+            if addr >= memory.len() {
+                break;
+            }
+
+            let marker = match (addr == self.pc.pc(), self.breakpoints.contains_key(&addr)) {
+                (true, true) => "*=>",
+                (true, false) => " =>",
+                (false, true) => "*  ",
+                (false, false) => "   "
+            };
+            match decode_in(memory, addr) {
+                Some(ins) => {
+                    writeln!(out, "{} {:04}: {}", marker, addr, ins.render(&BTreeSet::new()))?;
+                    addr += ins.width();
+                },
+                None => {
+                    writeln!(out, "{} {:04}: data {}", marker, addr, memory.get(addr))?;
+                    addr += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_breakpoint(text: &str) -> Option<Breakpoint> {
+    if let Ok(addr) = text.parse() {
+        return Some(Breakpoint::Addr(addr));
+    }
+    INSTRUCTIONS.iter().find(|(_, m, _)| *m == text).map(|&(op, _, _)| Breakpoint::Opcode(op))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn run(dbg: &mut Debugger, cmds: &[&str]) -> String {
+        let mut out = Vec::new();
+        for cmd in cmds {
+            dbg.execute(cmd, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step_and_inspect() {
+        // out = in * 2
+        let mut dbg = Debugger::new(vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+        assert_eq!(run(&mut dbg, &["step"]), "waiting for input at 0\n => 0000: input [9]\n");
+
+        let out = run(&mut dbg, &["input 21", "regs", "step 2", "mem 9"]);
+        assert_eq!(out, "pc 0  rb 0  queued input 1\n => 0006: output [9]\n0009: 42\n");
+
+        let out = run(&mut dbg, &["set 9 7", "step", "continue", "step"]);
+        assert_eq!(out, "output 7\n => 0008: halt\nhalted with 3\nhalted with 3\n");
+        assert_eq!(dbg.halted(), Some(&3));
+        assert!(!dbg.execute("quit", &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn breakpoints() {
+        // count down from 3, printing each value
+        let mut dbg = Debugger::new(vec![4, 11, 1001, 11, -1, 11, 1005, 11, 0, 99, 0, 3]);
+        let out = run(&mut dbg, &["break 6", "break output", "continue", "continue"]);
+        assert_eq!(out, "output 3\n\
+                         breakpoint at 6\n\
+                         *=> 0006: jnz [11], #0\n\
+                         breakpoint at 0\n\
+                         \x20=> 0000: output [11]\n");
+
+        let out = run(&mut dbg, &["breaks", "unbreak 6", "unbreak output", "unbreak add", "continue"]);
+        assert_eq!(out, "break 6\nbreak output\nno breakpoint \"add\"\noutput 2\noutput 1\nhalted with 4\n");
+    }
+
+    #[test]
+    fn list() {
+        let mut dbg = Debugger::new(vec![1101, 1, 2, 9, 1101, 3, 4, 10, 99, 0, 0]);
+        let out = run(&mut dbg, &["step 2", "list"]);
+        assert_eq!(out, " => 0008: halt\n\
+                         \x20   0000: add #1, #2, [9]\n\
+                         \x20   0004: add #3, #4, [10]\n\
+                         \x20=> 0008: halt\n\
+                         \x20   0009: input [7]\n");
+        assert!(run(&mut dbg, &["bogus"]).starts_with("unknown command"));
+    }
+
+    #[test]
+    fn far_memory() {
+        let mut dbg = Debugger::new(vec![1101, 7, 0, 1_000_000_000_000, 99]);
+        let out = run(&mut dbg, &["step", "list"]);
+        assert!(out.starts_with(" => 0004: halt\n\
+                                 \x20   0000: add #7, #0, [1000000000000]\n\
+                                 \x20=> 0004: halt\n\
+                                 \x20   0005: data 0\n"));
+        assert!(dbg.machine().memory().is_paged());
+
+        let out = run(&mut dbg, &["list 999999999999", "mem 999999999999 2", "mem 18446744073709551615 2"]);
+        assert_eq!(out, "    999999999999: data 0\n\
+                         \x20   1000000000000: data 7\n\
+                         999999999999: 0 7\n\
+                         range 18446744073709551615+2 is past the end of the address space\n");
+
+        // edits made from the debugger can't be undone, so neither can anything before them
+        let out = run(&mut dbg, &["set 1000000000000 8", "back", "mem 1000000000000"]);
+        assert_eq!(out, "journal exhausted\n => 0004: halt\n1000000000000: 8\n");
+    }
+
+    #[test]
+    fn conditions() {
+        let prog = vec![4, 11, 1001, 11, -1, 11, 1005, 11, 0, 99, 0, 3];
+        let mut dbg = Debugger::new(prog.clone());
+        let out = run(&mut dbg, &["break when [11] == 1", "continue", "continue"]);
+        assert_eq!(out, "output 3\n\
+                         output 2\n\
+                         break when [11] == 1 hit at 2: [11] 2 -> 1\n\
+                         \x20=> 0006: jnz [11], #0\n\
+                         output 1\n\
+                         halted with 4\n");
+
+        let mut dbg = Debugger::new(prog);
+        let out = run(&mut dbg, &["break output if [11]<3", "break 6 if rb > 0", "breaks", "continue"]);
+        assert_eq!(out, "break 6 if rb > 0\n\
+                         break output if [11] < 3\n\
+                         output 3\n\
+                         breakpoint at 0 if [11] < 3\n\
+                         \x20=> 0000: output [11]\n");
+
+        assert_eq!(Condition::<i64>::parse("pc >= 12").unwrap(), Condition {lhs: Value::Pc, cmp: Cmp::Ge, rhs: Value::Const(12)});
+        assert!(Condition::<i64>::parse("[x] == 1").is_err());
+        assert_eq!(run(&mut dbg, &["break when [1] 2"]), "no comparison in \"[1] 2\"\n");
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut dbg = Debugger::new(vec![4, 11, 1001, 11, -1, 11, 1005, 11, 0, 99, 0, 3]);
+        let out = run(&mut dbg, &["watch 11", "continue", "unwatch 11", "rwatch 11", "continue"]);
+        assert_eq!(out, "output 3\n\
+                         watch [11] written at 2: 3 -> 2\n\
+                         \x20=> 0006: jnz [11], #0\n\
+                         watch [11] read at 6: 2\n\
+                         \x20=> 0000: output [11]\n");
+        assert_eq!(run(&mut dbg, &["unwatch 5"]), "no watchpoint \"5\"\n");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("icdb-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut dbg = Debugger::new(vec![4, 11, 1001, 11, -1, 11, 1005, 11, 0, 99, 0, 3]);
+        let save = format!("save {}", path);
+        let load = format!("load {}", path);
+
+        let out = run(&mut dbg, &["step 2", &save, "continue", &load, "mem 11", "step"]);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(out, "output 3\n\
+                         \x20=> 0006: jnz [11], #0\n\
+                         output 2\n\
+                         output 1\n\
+                         halted with 4\n\
+                         \x20=> 0006: jnz [11], #0\n\
+                         0011: 2\n\
+                         \x20=> 0000: output [11]\n");
+        assert!(run(&mut dbg, &[&load]).contains("No such file"));
+    }
+
+    #[test]
+    fn back() {
+        let mut dbg = Debugger::new(vec![4, 11, 1001, 11, -1, 11, 1005, 11, 0, 99, 0, 3]);
+        let out = run(&mut dbg, &["continue", "back", "back 2", "mem 11", "back until 11", "mem 11", "back to 0", "back 100"]);
+        assert_eq!(out, "output 3\n\
+                         output 2\n\
+                         output 1\n\
+                         halted with 4\n\
+                         \x20=> 0009: halt\n\
+                         \x20=> 0002: add [11], #-1, [11]\n\
+                         0011: 1\n\
+                         \x20=> 0002: add [11], #-1, [11]\n\
+                         0011: 2\n\
+                         \x20=> 0000: output [11]\n\
+                         journal exhausted\n\
+                         \x20=> 0000: output [11]\n");
+    }
+}
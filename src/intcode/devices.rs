@@ -0,0 +1,304 @@
+use super::Word;
+use super::fuzz::Rng;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// A device answers loads and stores to the cells from the address it is
+// attached at. Device accesses go straight to the device: they are not
+// watched, journaled or traced as writes, and instruction fetches from a
+// mapped address still read plain memory.
+pub trait Device<W: Word> {
+    // cells taken up from the base address
+    fn size(&self) -> usize;
+
+    fn read(&mut self, offset: usize) -> W;
+
+    fn write(&mut self, offset: usize, val: W);
+}
+
+pub struct Bus<'d, W: Word> {
+    devices: Vec<(usize, &'d mut dyn Device<W>)>
+}
+
+impl<'d, W: Word> Bus<'d, W> {
+
+    pub fn new() -> Bus<'d, W> {
+        Bus {devices: Vec::new()}
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    // false if the device would overlap one already attached or run past
+    // the end of the address space
+    pub fn attach(&mut self, base: usize, device: &'d mut dyn Device<W>) -> bool {
+        let end = match base.checked_add(device.size()) {
+            Some(end) => end,
+            None => return false
+        };
+        if self.devices.iter().any(|(b, d)| base < b + d.size() && *b < end) {
+            return false;
+        }
+        self.devices.push((base, device));
+        true
+    }
+
+    fn find(&mut self, pos: usize) -> Option<(usize, &mut &'d mut dyn Device<W>)> {
+        self.devices.iter_mut().find(|(base, d)| pos >= *base && pos < base + d.size()).map(|(base, d)| (pos - *base, d))
+    }
+
+    // None where no device is mapped
+    pub fn read(&mut self, pos: usize) -> Option<W> {
+        self.find(pos).map(|(offset, device)| device.read(offset))
+    }
+
+    // false where no device is mapped
+    pub fn write(&mut self, pos: usize, val: W) -> bool {
+        match self.find(pos) {
+            Some((offset, device)) => {
+                device.write(offset, val);
+                true
+            },
+            None => false
+        }
+    }
+}
+
+impl<'d, W: Word> Default for Bus<'d, W> {
+    fn default() -> Self {
+        Bus::new()
+    }
+}
+
+// milliseconds since it was created or last written
+pub struct Clock(Instant);
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock(Instant::now())
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
+
+impl<W: Word> Device<W> for Clock {
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn read(&mut self, _: usize) -> W {
+        W::from_i64(self.0.elapsed().as_millis() as i64)
+    }
+
+    fn write(&mut self, _: usize, _: W) {
+        self.0 = Instant::now();
+    }
+}
+
+// a new number in 0 .. 2^31 on every read; a write reseeds it
+pub struct Random(Rng);
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random(Rng::new(seed))
+    }
+}
+
+impl<W: Word> Device<W> for Random {
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn read(&mut self, _: usize) -> W {
+        W::from_i64((self.0.next_u64() >> 33) as i64)
+    }
+
+    fn write(&mut self, _: usize, val: W) {
+        self.0 = Rng::new(val.to_i64().unwrap_or(0) as u64);
+    }
+}
+
+// width * height cells, row by row
+pub struct Framebuffer<W: Word = i64> {
+    width: usize,
+    pixels: Vec<W>
+}
+
+impl<W: Word> Framebuffer<W> {
+    pub fn new(width: usize, height: usize) -> Framebuffer<W> {
+        Framebuffer {width, pixels: vec![W::zero(); width * height]}
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &W {
+        &self.pixels[y * self.width + x]
+    }
+
+    pub fn pixels(&self) -> &[W] {
+        &self.pixels
+    }
+
+    // '#' for set pixels, '.' for zero ones
+    pub fn render(&self) -> String {
+        self.pixels.chunks(self.width).map(|row| {
+            row.iter().map(|p| if p.is_zero() { '.' } else { '#' }).chain(std::iter::once('\n')).collect::<String>()
+        }).collect()
+    }
+}
+
+impl<W: Word> Device<W> for Framebuffer<W> {
+    fn size(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn read(&mut self, offset: usize) -> W {
+        self.pixels[offset].clone()
+    }
+
+    fn write(&mut self, offset: usize, val: W) {
+        self.pixels[offset] = val;
+    }
+}
+
+// reading the first cell takes the next key code, 0 when there is none; the
+// second holds the number of keys waiting
+pub struct Keyboard {
+    keys: VecDeque<i64>
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard {keys: VecDeque::new()}
+    }
+
+    pub fn press(&mut self, text: &str) {
+        self.keys.extend(text.chars().map(|c| c as i64));
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Keyboard::new()
+    }
+}
+
+impl<W: Word> Device<W> for Keyboard {
+    fn size(&self) -> usize {
+        2
+    }
+
+    fn read(&mut self, offset: usize) -> W {
+        match offset {
+            0 => W::from_i64(self.keys.pop_front().unwrap_or(0)),
+            _ => W::from_i64(self.keys.len() as i64)
+        }
+    }
+
+    // any write to it drops the keys waiting
+    fn write(&mut self, _: usize, _: W) {
+        self.keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::{IntCodePC, IntcodeError, Limits};
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn bus() {
+        let mut clock = Clock::new();
+        let mut fb = Framebuffer::<i64>::new(2, 2);
+        let mut keys = Keyboard::new();
+        let mut other = Keyboard::new();
+        let mut bus = Bus::new();
+        assert!(bus.attach(10, &mut fb));
+        assert!(!bus.attach(13, &mut clock));
+        assert!(!bus.attach(9, &mut other));
+        assert!(bus.attach(14, &mut keys));
+        let mut far = Keyboard::new();
+        assert!(!bus.attach(usize::MAX - 1, &mut far));
+
+        assert!(bus.write(11, 5));
+        assert!(!bus.write(16, 5));
+        assert_eq!(bus.read(11), Some(5));
+        assert_eq!(bus.read(15), Some(0));
+        assert_eq!(bus.read(9), None);
+        drop(bus);
+        assert_eq!(fb.pixels(), &[0, 5, 0, 0]);
+    }
+
+    // copies the keys typed into the framebuffer, one per pixel
+    #[test]
+    fn keyboard_to_framebuffer() {
+        let prog = assemble("
+                arb #1000
+            again:
+                add [900], #0, [c]
+                jz [c], #done
+                add [c], #0, rb+0
+                arb #1
+                jz #0, #again
+            done:
+                halt
+            c:  data 0
+        ").unwrap();
+
+        let mut keys = Keyboard::new();
+        keys.press("ab\n");
+        let mut fb = Framebuffer::new(4, 2);
+        let mut pc = IntCodePC::new_detached(prog.clone());
+        assert!(pc.attach(900, &mut keys));
+        assert!(pc.attach(1000, &mut fb));
+        pc.enable_cache();
+        pc.run().unwrap();
+        assert_eq!(pc.memory().len(), prog.len());
+        drop(pc);
+
+        assert_eq!(fb.pixels(), &[97, 98, 10, 0, 0, 0, 0, 0]);
+        assert_eq!(fb.render(), "###.\n....\n");
+        assert_eq!(*fb.pixel(1, 0), 98);
+    }
+
+    #[test]
+    fn random() {
+        let mut a = Random::new(1);
+        let xs: Vec<i64> = (0 .. 100).map(|_| a.read(0)).collect();
+        assert!(xs.iter().all(|&x| (0 .. 1 << 31).contains(&x)));
+        assert!(xs.windows(2).any(|w| w[0] != w[1]));
+
+        Device::<i64>::write(&mut a, 0, 1);
+        assert_eq!(Device::<i64>::read(&mut a, 0), xs[0]);
+    }
+
+    // waits for the clock to pass 5ms; polling a device is I/O, so the loop
+    // is not taken for one that never ends
+    #[test]
+    fn clock() {
+        let prog = assemble("
+            again:
+                le [500], #5, [t]
+                jnz [t], #again
+                halt
+            t:  data 0
+        ").unwrap();
+
+        let mut clock = Clock::new();
+        let mut pc = IntCodePC::new_detached(prog.clone());
+        pc.set_limits(Limits {detect_loops: true, ..Default::default()});
+        assert!(pc.attach(500, &mut clock));
+        pc.run().unwrap();
+        drop(pc);
+        assert!(Device::<i64>::read(&mut clock, 0) >= 5);
+
+        let mut pc = IntCodePC::new_detached(prog);
+        pc.set_limits(Limits {detect_loops: true, ..Default::default()});
+        assert!(matches!(pc.run(), Err(IntcodeError::InfiniteLoop {..})));
+    }
+}
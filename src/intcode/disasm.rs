@@ -0,0 +1,290 @@
+use super::{Memory, ParamMode, Word, INSTRUCTIONS};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+const DATA_PER_LINE: usize = 6;
+
+// the opcode and the most parameters an instruction takes
+const MAX_WIDTH: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operand<W: Word = i64> {
+    pub mode: ParamMode,
+    pub value: W
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction<W: Word = i64> {
+    pub addr: usize,
+    pub opcode: i64,
+    pub mnemonic: &'static str,
+    pub operands: Vec<Operand<W>>
+}
+
+impl<W: Word> Instruction<W> {
+
+    // words taken up, the opcode and its parameters
+    pub fn width(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    pub fn encode(&self) -> i64 {
+        let mut ins = self.opcode;
+        let mut scale = 100;
+        for op in self.operands.iter() {
+            ins += scale * match op.mode {
+                ParamMode::Position => 0,
+                ParamMode::Immediate => 1,
+                ParamMode::Relative => 2
+            };
+            scale *= 10;
+        }
+        ins
+    }
+
+    // the statically known jump target, if this is a jump with an immediate target
+    pub fn target(&self) -> Option<usize> {
+        match self.opcode {
+            5 | 6 if self.operands[1].mode == ParamMode::Immediate => self.operands[1].value.to_address(),
+            _ => None
+        }
+    }
+
+    pub fn falls_through(&self) -> bool {
+        let cond = self.operands.first();
+        match (self.opcode, cond) {
+            (99, _) => false,
+            (5, Some(c)) if c.mode == ParamMode::Immediate => c.value.is_zero(),
+            (6, Some(c)) if c.mode == ParamMode::Immediate => !c.value.is_zero(),
+            _ => true
+        }
+    }
+
+    pub fn render(&self, labels: &BTreeSet<usize>) -> String {
+        let target = self.target().filter(|t| labels.contains(t));
+        let operands: Vec<String> = self.operands.iter().enumerate().map(|(n, op)| {
+            match (n, target) {
+                (1, Some(t)) => format!("#{}", label(t)),
+                _ => op.to_string()
+            }
+        }).collect();
+
+        if operands.is_empty() {
+            String::from(self.mnemonic)
+        } else {
+            format!("{} {}", self.mnemonic, operands.join(", "))
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Operand<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            ParamMode::Position => write!(f, "[{}]", self.value),
+            ParamMode::Immediate => write!(f, "#{}", self.value),
+            ParamMode::Relative => {
+                if self.value < W::zero() {
+                    write!(f, "rb{}", self.value)
+                } else {
+                    write!(f, "rb+{}", self.value)
+                }
+            }
+        }
+    }
+}
+
+pub fn label(addr: usize) -> String {
+    format!("l{}", addr)
+}
+
+// decodes the instruction at `addr`, None if the word there is not a
+// canonically encoded instruction or its parameters run past the image
+pub fn decode<W: Word>(image: &[W], addr: usize) -> Option<Instruction<W>> {
+    let ins = image.get(addr)?.to_i64()?;
+    if ins < 0 {
+        return None;
+    }
+
+    let &(opcode, mnemonic, arity) = INSTRUCTIONS.iter().find(|(code, _, _)| *code == ins % 100)?;
+    let mut modes = ins / 100;
+    let mut operands = Vec::new();
+    for n in 0 .. arity {
+        let mode = match modes % 10 {
+            0 => ParamMode::Position,
+            1 => ParamMode::Immediate,
+            2 => ParamMode::Relative,
+            _ => return None
+        };
+        modes /= 10;
+        operands.push(Operand {mode, value: image.get(addr + 1 + n)?.clone()});
+    }
+
+    if modes != 0 {
+        return None;
+    }
+
+    Some(Instruction {addr, opcode, mnemonic, operands})
+}
+
+// decodes the instruction at `addr` straight from memory, reading only the
+// words it can take up rather than the whole image
+pub fn decode_in<W: Word>(memory: &Memory<W>, addr: usize) -> Option<Instruction<W>> {
+    let end = addr.saturating_add(MAX_WIDTH).min(memory.len());
+    let window: Vec<W> = (addr .. end).map(|a| memory.get(a)).collect();
+    let mut ins = decode(&window, 0)?;
+    ins.addr = addr;
+    Some(ins)
+}
+
+pub struct Listing<W: Word = i64> {
+    pub code: BTreeMap<usize, Instruction<W>>,
+    pub labels: BTreeSet<usize>,
+    image: Vec<W>
+}
+
+// follows control flow from address 0, so that only words reachable as
+// instructions are decoded and everything else is kept as data
+pub fn disassemble<W: Word>(image: &[W]) -> Listing<W> {
+    let mut code = BTreeMap::new();
+    let mut labels = BTreeSet::new();
+    let mut claimed = BTreeSet::new();
+    let mut work = vec![0];
+
+    while let Some(addr) = work.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+
+        let ins = match decode(image, addr) {
+            Some(ins) => ins,
+            None => continue
+        };
+        if (addr .. addr + ins.width()).any(|a| claimed.contains(&a)) {
+            continue;
+        }
+        claimed.extend(addr .. addr + ins.width());
+
+        if let Some(target) = ins.target() {
+            labels.insert(target);
+            work.push(target);
+        }
+        if ins.falls_through() {
+            work.push(addr + ins.width());
+        }
+        code.insert(addr, ins);
+    }
+
+    // a jump into the middle of an instruction has no line to hang a label on
+    labels.retain(|addr| code.contains_key(addr));
+
+    Listing {code, labels, image: image.to_vec()}
+}
+
+impl<W: Word> Listing<W> {
+
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.code.contains_key(&addr)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut addr = 0;
+        while addr < self.image.len() {
+            if self.labels.contains(&addr) {
+                lines.push(format!("{}:", label(addr)));
+            }
+
+            match self.code.get(&addr) {
+                Some(ins) => {
+                    let raw: Vec<String> = self.image[addr .. addr + ins.width()].iter().map(|w| w.to_string()).collect();
+                    lines.push(format!("        {:<32}; {:04}: {}", ins.render(&self.labels), addr, raw.join(" ")));
+                    addr += ins.width();
+                },
+                None => {
+                    let start = addr;
+                    let mut words = Vec::new();
+                    while addr < self.image.len() && words.len() < DATA_PER_LINE && !self.is_code(addr) &&
+                        (addr == start || !self.labels.contains(&addr)) {
+                        words.push(self.image[addr].to_string());
+                        addr += 1;
+                    }
+                    lines.push(format!("        {:<32}; {:04}", format!("data {}", words.join(", ")), start));
+                }
+            }
+        }
+        lines
+    }
+}
+
+impl<W: Word> fmt::Display for Listing<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn decode_modes() {
+        let ins = decode(&[21101, 5, -3, 7], 0).unwrap();
+        assert_eq!(ins.mnemonic, "add");
+        assert_eq!(ins.encode(), 21101);
+        assert_eq!(ins.render(&BTreeSet::new()), "add #5, #-3, rb+7");
+
+        let ins = decode(&[1202, -1, 12, 0], 0).unwrap();
+        assert_eq!(ins.render(&BTreeSet::new()), "mul rb-1, #12, [0]");
+
+        assert!(decode(&[10099i64], 0).is_none());
+        assert!(decode(&[301i64, 0, 0, 0], 0).is_none());
+        assert!(decode(&[1i64, 0], 0).is_none());
+        assert!(decode(&[-1i64], 0).is_none());
+    }
+
+    #[test]
+    fn control_flow() {
+        // jump over a data word, then halt; the words after halt stay data
+        let image = vec![1105, 1, 4, 1234, 104, 7, 99, 30, 40];
+        let listing = disassemble(&image);
+
+        assert_eq!(listing.code.keys().cloned().collect::<Vec<_>>(), vec![0, 4, 6]);
+        assert!(listing.labels.contains(&4));
+        assert_eq!(listing.lines(), vec![
+            "        jnz #1, #l4                     ; 0000: 1105 1 4",
+            "        data 1234                       ; 0003",
+            "l4:",
+            "        output #7                       ; 0004: 104 7",
+            "        halt                            ; 0006: 99",
+            "        data 30, 40                     ; 0007",
+        ]);
+    }
+
+    #[test]
+    fn conditional() {
+        let image = vec![3, 9, 1005, 9, 7, 104, 0, 104, 1, 0];
+        let listing = disassemble(&image);
+        assert_eq!(listing.code.keys().cloned().collect::<Vec<_>>(), vec![0, 2, 5, 7]);
+        assert_eq!(listing.labels.iter().cloned().collect::<Vec<_>>(), vec![7]);
+        assert_eq!(listing.code[&2].render(&listing.labels), "jnz [9], #l7");
+    }
+
+    #[test]
+    fn jump_into_instruction() {
+        // the second jump lands on its own target operand
+        let image = vec![1106, 0, 3, 1105, 1, 4, 99];
+        let listing = disassemble(&image);
+        assert_eq!(listing.code.keys().cloned().collect::<Vec<_>>(), vec![0, 3]);
+        assert_eq!(listing.labels.iter().cloned().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(listing.lines(), vec![
+            "        jz #0, #l3                      ; 0000: 1106 0 3",
+            "l3:",
+            "        jnz #1, #4                      ; 0003: 1105 1 4",
+            "        data 99                         ; 0006",
+        ]);
+    }
+}
@@ -0,0 +1,78 @@
+use super::Word;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum IntcodeError<W: Word = i64> {
+    UnknownOpcode { pc: usize, ins: W },
+    BadParamMode { pc: usize, ins: W, mode: i64 },
+    BadAddress { pc: usize, ins: W, addr: W },
+    InputExhausted { pc: usize, ins: W },
+    MalformedInput { pc: usize, ins: W, input: String },
+    Overflow { pc: usize, ins: W },
+    Io { pc: usize, ins: W, error: std::io::Error },
+    StepLimit { pc: usize, ins: W },
+    MemoryLimit { pc: usize, ins: W, addr: usize },
+    Deadline { pc: usize, ins: W },
+    InfiniteLoop { pc: usize, ins: W }
+}
+
+impl<W: Word> IntcodeError<W> {
+    pub fn pc(&self) -> usize {
+        match self {
+            IntcodeError::UnknownOpcode { pc, .. } |
+            IntcodeError::BadParamMode { pc, .. } |
+            IntcodeError::BadAddress { pc, .. } |
+            IntcodeError::InputExhausted { pc, .. } |
+            IntcodeError::MalformedInput { pc, .. } |
+            IntcodeError::Overflow { pc, .. } |
+            IntcodeError::Io { pc, .. } |
+            IntcodeError::StepLimit { pc, .. } |
+            IntcodeError::MemoryLimit { pc, .. } |
+            IntcodeError::Deadline { pc, .. } |
+            IntcodeError::InfiniteLoop { pc, .. } => *pc
+        }
+    }
+
+    pub fn ins(&self) -> &W {
+        match self {
+            IntcodeError::UnknownOpcode { ins, .. } |
+            IntcodeError::BadParamMode { ins, .. } |
+            IntcodeError::BadAddress { ins, .. } |
+            IntcodeError::InputExhausted { ins, .. } |
+            IntcodeError::MalformedInput { ins, .. } |
+            IntcodeError::Overflow { ins, .. } |
+            IntcodeError::Io { ins, .. } |
+            IntcodeError::StepLimit { ins, .. } |
+            IntcodeError::MemoryLimit { ins, .. } |
+            IntcodeError::Deadline { ins, .. } |
+            IntcodeError::InfiniteLoop { ins, .. } => ins
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { pc, ins } => write!(f, "unknown opcode {} at {}", ins, pc),
+            IntcodeError::BadParamMode { pc, ins, mode } => write!(f, "bad parameter mode {} in {} at {}", mode, ins, pc),
+            IntcodeError::BadAddress { pc, ins, addr } => write!(f, "bad address {} in {} at {}", addr, ins, pc),
+            IntcodeError::InputExhausted { pc, ins } => write!(f, "input exhausted in {} at {}", ins, pc),
+            IntcodeError::MalformedInput { pc, ins, input } => write!(f, "malformed input {:?} in {} at {}", input, ins, pc),
+            IntcodeError::Overflow { pc, ins } => write!(f, "arithmetic overflow in {} at {}", ins, pc),
+            IntcodeError::Io { pc, ins, error } => write!(f, "i/o failure in {} at {}: {}", ins, pc, error),
+            IntcodeError::StepLimit { pc, ins } => write!(f, "step limit reached before {} at {}", ins, pc),
+            IntcodeError::MemoryLimit { pc, ins, addr } => write!(f, "store to {} past the memory limit in {} at {}", addr, ins, pc),
+            IntcodeError::Deadline { pc, ins } => write!(f, "deadline passed before {} at {}", ins, pc),
+            IntcodeError::InfiniteLoop { pc, ins } => write!(f, "infinite loop: state repeated without i/o before {} at {}", ins, pc)
+        }
+    }
+}
+
+impl<W: Word> std::error::Error for IntcodeError<W> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IntcodeError::Io { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
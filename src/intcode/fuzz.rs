@@ -0,0 +1,375 @@
+use super::{IntCodePC, IntcodeError, Opcode};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Inputs are mutated from the ones that reached new addresses, so the search
+// works its way past comparisons one branch at a time; the constants in the
+// image are tried as values since those are what inputs get compared with.
+const INTERESTING: [i64; 9] = [0, 1, -1, 2, 10, 99, 1000, i64::MAX, i64::MIN];
+
+// xorshift64*, enough for picking mutations
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15 | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // uniform in 0 .. n, n > 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn chance(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Fault {
+    InvalidOpcode(usize),
+    BadAddress(usize),
+    Overflow(usize),
+    Hang
+}
+
+impl Fault {
+    // for file names in the corpus
+    pub fn name(&self) -> String {
+        match self {
+            Fault::InvalidOpcode(pc) => format!("invalid-opcode-{:04}", pc),
+            Fault::BadAddress(pc) => format!("bad-address-{:04}", pc),
+            Fault::Overflow(pc) => format!("overflow-{:04}", pc),
+            Fault::Hang => String::from("hang")
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::InvalidOpcode(pc) => write!(f, "invalid opcode at {}", pc),
+            Fault::BadAddress(pc) => write!(f, "bad address at {}", pc),
+            Fault::Overflow(pc) => write!(f, "arithmetic overflow at {}", pc),
+            Fault::Hang => write!(f, "no halt within the step budget")
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Halted,
+    // stopped for input after using up all it was given
+    Starved,
+    Fault(Fault)
+}
+
+// runs `image` on `input` for at most `steps` instructions, adding the
+// addresses it executes to `covered`
+pub fn execute(image: &[i64], input: &[i64], steps: u64, covered: &mut HashSet<usize>) -> Outcome {
+    let mut pc = IntCodePC::new_detached(image.to_vec());
+    for &val in input {
+        pc.feed(val);
+    }
+    for _ in 0 .. steps {
+        covered.insert(pc.pc());
+        match pc.step_yielding() {
+            Ok((Opcode::Halt(_), _)) => return Outcome::Halted,
+            Ok(_) => {},
+            Err(IntcodeError::UnknownOpcode {pc, ..}) | Err(IntcodeError::BadParamMode {pc, ..}) => return Outcome::Fault(Fault::InvalidOpcode(pc)),
+            Err(IntcodeError::BadAddress {pc, ..}) => return Outcome::Fault(Fault::BadAddress(pc)),
+            Err(IntcodeError::Overflow {pc, ..}) => return Outcome::Fault(Fault::Overflow(pc)),
+            Err(_) => return Outcome::Starved
+        }
+    }
+    Outcome::Fault(Fault::Hang)
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub seed: u64,
+    // instructions before a run counts as hung
+    pub steps: u64,
+    // longest input generated
+    pub max_len: usize
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {seed: 1, steps: 100_000, max_len: 16}
+    }
+}
+
+pub struct Fuzzer {
+    image: Vec<i64>,
+    options: Options,
+    rng: Rng,
+    dict: Vec<i64>,
+    queue: Vec<Vec<i64>>,
+    covered: HashSet<usize>,
+    crashes: BTreeMap<Fault, Vec<i64>>,
+    execs: u64
+}
+
+impl Fuzzer {
+
+    pub fn new(image: Vec<i64>, options: Options) -> Fuzzer {
+        let dict: BTreeSet<i64> = image.iter().cloned().chain(INTERESTING.iter().cloned()).collect();
+        let mut fuzzer = Fuzzer {
+            rng: Rng::new(options.seed),
+            image,
+            options,
+            dict: dict.into_iter().collect(),
+            queue: Vec::new(),
+            covered: HashSet::new(),
+            crashes: BTreeMap::new(),
+            execs: 0
+        };
+        fuzzer.try_input(Vec::new());
+        fuzzer
+    }
+
+    pub fn add_seed(&mut self, input: Vec<i64>) {
+        if !self.try_input(input.clone()) {
+            self.queue.push(input);
+        }
+    }
+
+    // runs `execs` more mutated inputs
+    pub fn run(&mut self, execs: u64) {
+        for _ in 0 .. execs {
+            let input = if self.queue.is_empty() || self.rng.chance(8) {
+                let len = 1 + self.rng.below(self.options.max_len);
+                (0 .. len).map(|_| self.value()).collect()
+            } else {
+                let parent = self.queue[self.rng.below(self.queue.len())].clone();
+                let n = 1 + self.rng.below(4);
+                (0 .. n).fold(parent, |input, _| self.mutate(input))
+            };
+            self.try_input(input);
+        }
+    }
+
+    // runs one input, keeping it if it reached new code; true if it did
+    fn try_input(&mut self, input: Vec<i64>) -> bool {
+        let before = self.covered.len();
+        let outcome = execute(&self.image, &input, self.options.steps, &mut self.covered);
+        self.execs += 1;
+        if let Outcome::Fault(fault) = outcome {
+            if !self.crashes.contains_key(&fault) {
+                let input = self.minimize(input, fault);
+                self.crashes.insert(fault, input);
+            }
+            return false;
+        }
+        if self.covered.len() > before {
+            self.queue.push(input);
+            return true;
+        }
+        false
+    }
+
+    fn value(&mut self) -> i64 {
+        match self.rng.below(3) {
+            0 => self.dict[self.rng.below(self.dict.len())],
+            _ => self.rng.below(201) as i64 - 100
+        }
+    }
+
+    fn mutate(&mut self, mut input: Vec<i64>) -> Vec<i64> {
+        let len = input.len();
+        match self.rng.below(6) {
+            0 if len < self.options.max_len => {
+                let at = self.rng.below(len + 1);
+                let val = self.value();
+                input.insert(at, val);
+            },
+            1 if len > 0 => {
+                input.remove(self.rng.below(len));
+            },
+            2 if len > 0 => {
+                let at = self.rng.below(len);
+                let delta = self.rng.below(33) as i64 - 16;
+                input[at] = input[at].wrapping_add(delta);
+            },
+            3 if !self.queue.is_empty() => {
+                let other = &self.queue[self.rng.below(self.queue.len())];
+                let at = self.rng.below(len + 1);
+                let from = self.rng.below(other.len() + 1);
+                input.truncate(at);
+                input.extend_from_slice(&other[from ..]);
+                input.truncate(self.options.max_len);
+            },
+            _ if len > 0 => {
+                let at = self.rng.below(len);
+                input[at] = self.value();
+            },
+            _ => input.push(self.value())
+        }
+        input
+    }
+
+    // drops what it can from `input`, then makes the rest small, as long as
+    // it keeps failing the same way
+    fn minimize(&mut self, mut input: Vec<i64>, fault: Fault) -> Vec<i64> {
+        let mut fails = |input: &[i64]| {
+            self.execs += 1;
+            execute(&self.image, input, self.options.steps, &mut HashSet::new()) == Outcome::Fault(fault)
+        };
+
+        let mut chunk = input.len().div_ceil(2);
+        while chunk > 0 {
+            let mut at = 0;
+            while at < input.len() {
+                let end = usize::min(at + chunk, input.len());
+                let shorter: Vec<i64> = input[.. at].iter().chain(&input[end ..]).cloned().collect();
+                if fails(&shorter) {
+                    input = shorter;
+                } else {
+                    at += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+
+        for at in 0 .. input.len() {
+            while input[at] != 0 {
+                let val = input[at];
+                let smaller = [0, val / 2].iter().map(|&v| {
+                    let mut input = input.clone();
+                    input[at] = v;
+                    input
+                }).find(|input| fails(input));
+                match smaller {
+                    Some(smaller) => input = smaller,
+                    None => break
+                }
+            }
+        }
+        input
+    }
+
+    pub fn execs(&self) -> u64 {
+        self.execs
+    }
+
+    pub fn coverage(&self) -> usize {
+        self.covered.len()
+    }
+
+    pub fn queue(&self) -> &[Vec<i64>] {
+        &self.queue
+    }
+
+    // each fault found with the smallest input that showed it
+    pub fn crashes(&self) -> &BTreeMap<Fault, Vec<i64>> {
+        &self.crashes
+    }
+
+    // writes each crash to `dir` as <fault>.txt, one value per line so that
+    // it can be piped into the program; returns the paths written
+    pub fn write_crashes(&self, dir: &Path) -> io::Result<Vec<String>> {
+        fs::create_dir_all(dir)?;
+        let mut paths = Vec::new();
+        for (fault, input) in self.crashes.iter() {
+            let path = dir.join(format!("{}.txt", fault.name()));
+            let text: String = input.iter().map(|val| format!("{}\n", val)).collect();
+            fs::write(&path, text)?;
+            paths.push(path.display().to_string());
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    // jumps into data only for the inputs 13 then -8; hangs on an input of
+    // 0 after that, and has a bad address for a negative one
+    const SRC: &str = "
+            input [a]
+            eq [a], #13, [t]
+            jz [t], #done
+            input [a]
+            eq [a], #-8, [t]
+            jnz [t], #bad
+            input [a]
+        spin:
+            jz [a], #spin
+            arb [a]
+            add rb+0, #0, [t]
+        done:
+            halt
+        bad:
+            data 77
+        a:  data 0
+        t:  data 0
+    ";
+
+    #[test]
+    fn rng() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        let xs: Vec<u64> = (0 .. 100).map(|_| a.next_u64()).collect();
+        assert!(xs.iter().all(|&x| x == b.next_u64()));
+        assert!((0 .. 1000).all(|_| a.below(10) < 10));
+        assert_ne!(Rng::new(8).next_u64(), xs[0]);
+    }
+
+    #[test]
+    fn outcomes() {
+        let prog = assemble(SRC).unwrap();
+        let mut covered = HashSet::new();
+        assert_eq!(execute(&prog, &[1], 1000, &mut covered), Outcome::Halted);
+        assert_eq!(covered.len(), 4);
+        assert_eq!(execute(&prog, &[13], 1000, &mut covered), Outcome::Starved);
+        assert_eq!(execute(&prog, &[13, -8], 1000, &mut covered), Outcome::Fault(Fault::InvalidOpcode(30)));
+        assert_eq!(execute(&prog, &[13, 1, 0], 1000, &mut covered), Outcome::Fault(Fault::Hang));
+        assert_eq!(execute(&prog, &[13, 1, -5], 1000, &mut covered), Outcome::Fault(Fault::BadAddress(25)));
+    }
+
+    #[test]
+    fn finds_crashes() {
+        let prog = assemble(SRC).unwrap();
+        let mut fuzzer = Fuzzer::new(prog, Options {seed: 3, steps: 1000, max_len: 8});
+        fuzzer.run(20_000);
+
+        let crashes: Vec<(Fault, Vec<i64>)> = fuzzer.crashes().iter().map(|(f, i)| (*f, i.clone())).collect();
+        assert_eq!(crashes, vec![
+            (Fault::InvalidOpcode(30), vec![13, -8]),
+            (Fault::BadAddress(25), vec![13, 0, -1]),
+            (Fault::Hang, vec![13, 0, 0])
+        ]);
+        assert_eq!(fuzzer.coverage(), 12);
+    }
+
+    #[test]
+    fn corpus() {
+        let prog = assemble(SRC).unwrap();
+        let mut fuzzer = Fuzzer::new(prog, Options::default());
+        fuzzer.add_seed(vec![13, -8]);
+        assert_eq!(fuzzer.crashes().len(), 1);
+
+        let dir = std::env::temp_dir().join(format!("intcode-fuzz-{}", std::process::id()));
+        let paths = fuzzer.write_crashes(&dir).unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("invalid-opcode-0030.txt"));
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), "13\n-8\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use super::Word;
+use std::collections::VecDeque;
+use std::io::{BufRead, Error, ErrorKind, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+pub trait InputPort<W: Word> {
+    // Ok(None) means the input is exhausted
+    fn read_word(&mut self) -> std::io::Result<Option<W>>;
+}
+
+pub trait OutputPort<W: Word> {
+    fn write_word(&mut self, val: W) -> std::io::Result<()>;
+}
+
+impl<W: Word> InputPort<W> for VecDeque<W> {
+    fn read_word(&mut self) -> std::io::Result<Option<W>> {
+        Ok(self.pop_front())
+    }
+}
+
+impl<W: Word> OutputPort<W> for VecDeque<W> {
+    fn write_word(&mut self, val: W) -> std::io::Result<()> {
+        self.push_back(val);
+        Ok(())
+    }
+}
+
+impl<W: Word> OutputPort<W> for Vec<W> {
+    fn write_word(&mut self, val: W) -> std::io::Result<()> {
+        self.push(val);
+        Ok(())
+    }
+}
+
+impl<W: Word> InputPort<W> for Receiver<W> {
+    fn read_word(&mut self) -> std::io::Result<Option<W>> {
+        Ok(self.recv().ok())
+    }
+}
+
+impl<W: Word> OutputPort<W> for Sender<W> {
+    fn write_word(&mut self, val: W) -> std::io::Result<()> {
+        self.send(val).map_err(|_| Error::new(ErrorKind::BrokenPipe, "receiver disconnected"))
+    }
+}
+
+impl<W: Word, F: FnMut() -> Option<W>> InputPort<W> for F {
+    fn read_word(&mut self) -> std::io::Result<Option<W>> {
+        Ok(self())
+    }
+}
+
+impl<W: Word, F: FnMut(W)> OutputPort<W> for F {
+    fn write_word(&mut self, val: W) -> std::io::Result<()> {
+        self(val);
+        Ok(())
+    }
+}
+
+// one decimal value per line, as typed at a terminal
+pub struct TextInput<R: BufRead>(pub R);
+
+pub struct TextOutput<O: Write>(pub O);
+
+impl<W: Word, R: BufRead> InputPort<W> for TextInput<R> {
+    fn read_word(&mut self) -> std::io::Result<Option<W>> {
+        let mut buf = String::new();
+        let read = self.0.read_line(&mut buf)?;
+        parse_line(read, buf)
+    }
+}
+
+impl<W: Word> InputPort<W> for &std::io::Stdin {
+    fn read_word(&mut self) -> std::io::Result<Option<W>> {
+        let mut buf = String::new();
+        let read = self.read_line(&mut buf)?;
+        parse_line(read, buf)
+    }
+}
+
+fn parse_line<W: Word>(read: usize, buf: String) -> std::io::Result<Option<W>> {
+    match read {
+        0 => Ok(None),
+        _ => match W::parse(buf.trim()) {
+            Ok(val) => Ok(Some(val)),
+            Err(_) => Err(Error::new(ErrorKind::InvalidData, buf))
+        }
+    }
+}
+
+impl<W: Word, O: Write> OutputPort<W> for TextOutput<O> {
+    fn write_word(&mut self, val: W) -> std::io::Result<()> {
+        writeln!(self.0, "{}", val)
+    }
+}
+
+// one character code per value: typed lines go in a character at a time,
+// newline included, and output in 0..=127 comes out as characters
+pub struct AsciiInput<R: BufRead> {
+    reader: R,
+    pending: VecDeque<i64>
+}
+
+impl<R: BufRead> AsciiInput<R> {
+    pub fn new(reader: R) -> AsciiInput<R> {
+        AsciiInput {reader, pending: VecDeque::new()}
+    }
+}
+
+impl<W: Word, R: BufRead> InputPort<W> for AsciiInput<R> {
+    fn read_word(&mut self) -> std::io::Result<Option<W>> {
+        if self.pending.is_empty() {
+            let mut buf = String::new();
+            if self.reader.read_line(&mut buf)? == 0 {
+                return Ok(None);
+            }
+            let line = buf.trim_end_matches(&['\r', '\n'][..]);
+            self.pending.extend(line.chars().map(|c| c as i64));
+            self.pending.push_back(10);
+        }
+        Ok(self.pending.pop_front().map(W::from_i64))
+    }
+}
+
+// anything else, usually the answer, goes on a line of its own as a number
+pub struct AsciiOutput<O: Write> {
+    writer: O,
+    line_start: bool
+}
+
+impl<O: Write> AsciiOutput<O> {
+    pub fn new(writer: O) -> AsciiOutput<O> {
+        AsciiOutput {writer, line_start: true}
+    }
+
+    pub fn into_inner(self) -> O {
+        self.writer
+    }
+
+    // for a buffered writer, before waiting on the user
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Word, O: Write> OutputPort<W> for AsciiOutput<O> {
+    fn write_word(&mut self, val: W) -> std::io::Result<()> {
+        match val.to_i64() {
+            Some(c) if (0 ..= 127).contains(&c) => {
+                self.writer.write_all(&[c as u8])?;
+                self.line_start = c == 10;
+            },
+            _ => {
+                if !self.line_start {
+                    writeln!(self.writer)?;
+                }
+                writeln!(self.writer, "{}", val)?;
+                self.line_start = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn text() {
+        let buf = b"12\n-3\nx\n";
+        let mut i = TextInput(&buf[..]);
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap(), Some(12));
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap(), Some(-3));
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap(), None);
+
+        let mut o = TextOutput(Vec::new());
+        o.write_word(5i64).unwrap();
+        o.write_word(-6i64).unwrap();
+        assert_eq!(String::from_utf8(o.0).unwrap(), "5\n-6\n");
+    }
+
+    #[test]
+    fn ascii() {
+        let buf = b"hi\r\n\nno";
+        let mut i = AsciiInput::new(&buf[..]);
+        let codes: Vec<i64> = std::iter::from_fn(|| i.read_word().unwrap()).collect();
+        assert_eq!(codes, vec![104, 105, 10, 10, 110, 111, 10]);
+
+        let mut o = AsciiOutput::new(Vec::new());
+        for &val in [72i64, 105, 10, 12345, 65, -1, 10].iter() {
+            o.write_word(val).unwrap();
+        }
+        assert_eq!(String::from_utf8(o.into_inner()).unwrap(), "Hi\n12345\nA\n-1\n\n");
+    }
+
+    #[test]
+    fn channel() {
+        let (tx, mut rx) = std::sync::mpsc::channel();
+        let mut tx2 = tx.clone();
+        tx2.write_word(1i64).unwrap();
+        drop(tx);
+        drop(tx2);
+        assert_eq!(rx.read_word().unwrap(), Some(1));
+        assert_eq!(rx.read_word().unwrap(), None);
+    }
+
+    #[test]
+    fn closures() {
+        let mut next = 0;
+        let mut i = || { next += 1; if next < 3 { Some(next) } else { None } };
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap(), Some(1));
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap(), Some(2));
+        assert_eq!(InputPort::<i64>::read_word(&mut i).unwrap(), None);
+
+        let mut sum = 0;
+        let mut o = |v: i64| sum += v;
+        o.write_word(2).unwrap();
+        o.write_word(3).unwrap();
+        assert_eq!(sum, 5);
+    }
+}
@@ -0,0 +1,140 @@
+use super::Word;
+use std::collections::VecDeque;
+
+// what undoing one instruction takes: the registers and memory size before
+// it, the old value of every cell it wrote, and the input it consumed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step<W: Word = i64> {
+    pub pc: usize,
+    pub rb: W,
+    pub len: usize,
+    pub writes: Vec<(usize, W)>,
+    pub input: Option<W>
+}
+
+impl<W: Word> Step<W> {
+
+    pub fn new(pc: usize, rb: W, len: usize) -> Step<W> {
+        Step {pc, rb, len, writes: Vec::new(), input: None}
+    }
+
+    pub fn wrote(&self, addr: usize) -> bool {
+        self.writes.iter().any(|(a, _)| *a == addr)
+    }
+}
+
+// the last `cap` steps, oldest first; older ones are forgotten
+#[derive(Clone, Debug)]
+pub struct Journal<W: Word = i64> {
+    cap: usize,
+    steps: VecDeque<Step<W>>
+}
+
+impl<W: Word> Journal<W> {
+
+    pub fn new(cap: usize) -> Journal<W> {
+        Journal {cap, steps: VecDeque::new()}
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn push(&mut self, step: Step<W>) {
+        if self.cap == 0 {
+            return;
+        }
+        if self.steps.len() == self.cap {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+    }
+
+    pub fn last(&self) -> Option<&Step<W>> {
+        self.steps.back()
+    }
+
+    pub fn pop(&mut self) -> Option<Step<W>> {
+        self.steps.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::intcode::{IntCodePC, State};
+
+    #[test]
+    fn step_back() {
+        // [11] = in * 3, [12] = [11] + 1, growing memory by one cell
+        let prog = vec![3,11,1002,11,3,11,1001,11,1,12,99,0];
+        let mut pc = IntCodePC::new_detached(prog.clone());
+        pc.enable_journal(16);
+        pc.feed(4);
+        pc.run().unwrap();
+        assert_eq!(pc.memory().len(), 13);
+        assert_eq!(pc.memory().get(12), 13);
+        assert_eq!(pc.journal().unwrap().len(), 4);
+
+        assert!(pc.step_back());
+        assert!(pc.step_back());
+        assert_eq!(pc.pc(), 6);
+        assert_eq!(pc.memory().len(), 12);
+        assert_eq!(pc.memory().get(11), 12);
+
+        // undoing the input puts the value back so that it is read again
+        assert!(pc.run_back_to(0));
+        assert_eq!(pc.memory().to_vec(), prog);
+        assert_eq!(pc.pending_input(), 1);
+        assert!(!pc.step_back());
+        assert_eq!(pc.run().unwrap(), 3);
+        assert_eq!(pc.memory().get(12), 13);
+    }
+
+    #[test]
+    fn last_write() {
+        // count [14] down from 3 while adding it to [15]
+        let prog = vec![1,14,15,15,1001,14,-1,14,1005,14,0,4,15,99,3,0];
+        let mut pc = IntCodePC::new_detached(prog);
+        pc.enable_journal(100);
+        assert_eq!(pc.resume().unwrap(), State::Output(6));
+
+        assert!(pc.run_back_to_write(15));
+        assert_eq!(pc.pc(), 0);
+        assert_eq!(pc.memory().get(14), 1);
+        assert_eq!(pc.memory().get(15), 5);
+        assert!(pc.run_back_to_write(14));
+        assert_eq!(pc.pc(), 4);
+        assert_eq!(pc.memory().get(14), 2);
+        assert!(!pc.run_back_to(11));
+        assert_eq!(pc.memory().get(15), 0);
+    }
+
+    #[test]
+    fn bounded() {
+        let mut pc = IntCodePC::new_detached(vec![1101,1,1,0,1101,2,2,0,1101,3,3,0,99]);
+        pc.enable_journal(2);
+        pc.run().unwrap();
+        assert!(pc.step_back());
+        assert!(pc.step_back());
+        assert!(!pc.step_back());
+        assert_eq!(pc.pc(), 8);
+        assert_eq!(pc.memory().get(0), 4);
+
+        pc.disable_journal();
+        pc.run().unwrap();
+        assert!(!pc.step_back());
+    }
+}
@@ -0,0 +1,177 @@
+use super::{Memory, Word};
+use std::collections::HashSet;
+use std::time::Instant;
+
+// instructions between reads of the clock
+const CLOCK_EVERY: u64 = 1024;
+
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    // instructions executed from when the limits were set
+    pub max_steps: Option<u64>,
+    // cells; a store at or past this address fails
+    pub max_memory: Option<usize>,
+    pub deadline: Option<Instant>,
+    // fail when the machine gets back to a state it was in without doing
+    // any I/O in between, since it would go round forever
+    pub detect_loops: bool
+}
+
+pub(super) enum Exceeded {
+    Steps,
+    Deadline,
+    Loop
+}
+
+// Loops are found with Brent's algorithm: the state is saved at every power
+// of two instructions since the last I/O, and each later state is compared
+// with it. Memory is compared through the set of addresses stores have made
+// differ from the saved copy, which keeps the comparison cheap.
+pub(super) struct Guard<W: Word> {
+    pub limits: Limits,
+    steps: u64,
+    saved: Option<(usize, W, Memory<W>)>,
+    changed: HashSet<usize>,
+    power: u64,
+    since: u64
+}
+
+impl<W: Word> Guard<W> {
+
+    pub fn new(limits: Limits) -> Guard<W> {
+        Guard {limits, steps: 0, saved: None, changed: HashSet::new(), power: 1, since: 0}
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // called before each instruction
+    pub fn check(&mut self, pc: usize, rb: &W, memory: &Memory<W>) -> Option<Exceeded> {
+        if self.limits.max_steps.is_some_and(|max| self.steps >= max) {
+            return Some(Exceeded::Steps);
+        }
+        if self.steps.is_multiple_of(CLOCK_EVERY) && self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Some(Exceeded::Deadline);
+        }
+
+        if self.limits.detect_loops {
+            if let Some((saved_pc, saved_rb, _)) = &self.saved {
+                if *saved_pc == pc && saved_rb == rb && self.changed.is_empty() {
+                    return Some(Exceeded::Loop);
+                }
+            }
+            if self.saved.is_none() || self.since == self.power {
+                self.saved = Some((pc, rb.clone(), memory.clone()));
+                self.changed.clear();
+                self.power *= 2;
+                self.since = 0;
+            }
+            self.since += 1;
+        }
+        self.steps += 1;
+        None
+    }
+
+    pub fn wrote(&mut self, pos: usize, val: &W) {
+        if let Some((_, _, memory)) = &self.saved {
+            if memory.get(pos) == *val {
+                self.changed.remove(&pos);
+            } else {
+                self.changed.insert(pos);
+            }
+        }
+    }
+
+    // drops the saved state after I/O, or edits the guard did not see
+    pub fn forget(&mut self) {
+        self.saved = None;
+        self.changed.clear();
+        self.power = 1;
+        self.since = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::{IntCodePC, IntcodeError};
+    use std::time::Duration;
+
+    fn limited(prog: Vec<i64>, limits: Limits) -> IntCodePC<'static, 'static> {
+        let mut pc = IntCodePC::new_detached(prog);
+        pc.set_limits(limits);
+        pc
+    }
+
+    #[test]
+    fn steps() {
+        let mut pc = limited(vec![1101,1,0,7,1105,1,0,0], Limits {max_steps: Some(101), ..Default::default()});
+        match pc.run() {
+            Err(IntcodeError::StepLimit {pc: 4, ..}) => {},
+            res => panic!("{:?}", res)
+        }
+        assert_eq!(pc.executed(), Some(101));
+
+        // stopped before the instruction, so raising the limit carries on
+        let mut pc = limited(vec![1101,1,0,7,99], Limits {max_steps: Some(1), ..Default::default()});
+        assert!(pc.step_yielding().is_ok());
+        assert!(pc.step_yielding().is_err());
+        assert_eq!(pc.pc(), 4);
+        pc.set_limits(Limits::default());
+        assert_eq!(pc.run().unwrap(), 1101);
+    }
+
+    #[test]
+    fn memory() {
+        let prog = vec![1101,1,1,1_000_000,99];
+        assert!(IntCodePC::new_detached(prog.clone()).run().is_ok());
+
+        let mut pc = limited(prog.clone(), Limits {max_memory: Some(4096), ..Default::default()});
+        match pc.run() {
+            Err(IntcodeError::MemoryLimit {pc: 0, addr: 1_000_000, ..}) => {},
+            res => panic!("{:?}", res)
+        }
+        assert_eq!(pc.memory().len(), 5);
+
+        let mut pc = limited(prog, Limits {max_memory: Some(4096), ..Default::default()});
+        pc.enable_cache();
+        assert!(matches!(pc.run(), Err(IntcodeError::MemoryLimit {..})));
+    }
+
+    #[test]
+    fn deadline() {
+        let prog = vec![1105,1,0];
+        let mut pc = limited(prog.clone(), Limits {deadline: Some(Instant::now()), ..Default::default()});
+        assert!(matches!(pc.run(), Err(IntcodeError::Deadline {pc: 0, ..})));
+
+        let mut pc = limited(prog, Limits {deadline: Some(Instant::now() + Duration::from_millis(20)), ..Default::default()});
+        assert!(matches!(pc.run(), Err(IntcodeError::Deadline {..})));
+        assert!(pc.executed().unwrap() > 0);
+    }
+
+    #[test]
+    fn loops() {
+        let detect = || Limits {detect_loops: true, max_steps: Some(100_000), ..Default::default()};
+
+        // counts [12] down from 5000, then halts: never the same state twice
+        let mut pc = limited(vec![1001,12,-1,12,1005,12,0,99,0,0,0,0,5000], detect());
+        assert_eq!(pc.run().unwrap(), 1001);
+
+        // flips [8] between 0 and 1 forever: a state repeats every 4 steps
+        let mut pc = limited(vec![1007,8,1,8,1105,1,0,0,0], detect());
+        match pc.run() {
+            Err(IntcodeError::InfiniteLoop {..}) => {},
+            res => panic!("{:?}", res)
+        }
+        assert!(pc.executed().unwrap() < 20);
+
+        // the same with an output in the loop is not stuck, only long
+        let mut out = Vec::new();
+        let mut i = std::collections::VecDeque::new();
+        let mut pc = IntCodePC::new_with_io(vec![1007,10,1,10,4,10,1105,1,0,0,0], &mut i, &mut out);
+        pc.set_limits(detect());
+        assert!(matches!(pc.run(), Err(IntcodeError::StepLimit {..})));
+    }
+}
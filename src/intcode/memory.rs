@@ -0,0 +1,206 @@
+use super::Word;
+use std::collections::HashMap;
+
+pub const PAGE_SIZE: usize = 1024;
+
+// writes further than this past the end of a flat image switch it to pages
+const SPARSE_GAP: usize = 1 << 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Footprint {
+    pub size: usize,
+    pub allocated: usize,
+    pub pages: usize
+}
+
+#[derive(Clone, Debug)]
+enum Backend<W: Word> {
+    Flat(Vec<W>),
+    Paged(HashMap<usize, Vec<W>>)
+}
+
+#[derive(Clone, Debug)]
+pub struct Memory<W: Word> {
+    backend: Backend<W>,
+    size: usize
+}
+
+impl<W: Word> Memory<W> {
+
+    pub fn flat(image: Vec<W>) -> Memory<W> {
+        let size = image.len();
+        Memory {backend: Backend::Flat(image), size}
+    }
+
+    pub fn paged(image: Vec<W>) -> Memory<W> {
+        let size = image.len();
+        let mut pages = HashMap::new();
+        for (id, chunk) in image.chunks(PAGE_SIZE).enumerate() {
+            let mut page = chunk.to_vec();
+            page.resize(PAGE_SIZE, W::zero());
+            pages.insert(id, page);
+        }
+        Memory {backend: Backend::Paged(pages), size}
+    }
+
+    pub fn is_paged(&self) -> bool {
+        matches!(self.backend, Backend::Paged(_))
+    }
+
+    pub fn get(&self, addr: usize) -> W {
+        match &self.backend {
+            Backend::Flat(cells) => {
+                cells.get(addr).cloned().unwrap_or_else(W::zero)
+            },
+            Backend::Paged(pages) => {
+                match pages.get(&(addr / PAGE_SIZE)) {
+                    Some(page) => page[addr % PAGE_SIZE].clone(),
+                    None => W::zero()
+                }
+            }
+        }
+    }
+
+    pub fn set(&mut self, addr: usize, val: W) {
+        if let Backend::Flat(cells) = &mut self.backend {
+            if addr >= cells.len() + SPARSE_GAP {
+                let image = std::mem::take(cells);
+                *self = Memory::paged(image);
+            }
+        }
+
+        match &mut self.backend {
+            Backend::Flat(cells) => {
+                if addr >= cells.len() {
+                    cells.resize(addr + 1, W::zero());
+                }
+                cells[addr] = val;
+            },
+            Backend::Paged(pages) => {
+                let page = pages.entry(addr / PAGE_SIZE).or_insert_with(|| vec![W::zero(); PAGE_SIZE]);
+                page[addr % PAGE_SIZE] = val;
+            }
+        }
+
+        self.size = usize::max(self.size, addr + 1);
+    }
+
+    // shrinks the image back to `len` cells, dropping whatever was past it
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.size {
+            return;
+        }
+        match &mut self.backend {
+            Backend::Flat(cells) => cells.truncate(len),
+            Backend::Paged(pages) => {
+                pages.retain(|id, _| id * PAGE_SIZE < len);
+                if let Some(page) = pages.get_mut(&(len / PAGE_SIZE)) {
+                    page[len % PAGE_SIZE ..].iter_mut().for_each(|w| *w = W::zero());
+                }
+            }
+        }
+        self.size = len;
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn to_vec(&self) -> Vec<W> {
+        match &self.backend {
+            Backend::Flat(cells) => cells.clone(),
+            Backend::Paged(_) => (0 .. self.size).map(|addr| self.get(addr)).collect()
+        }
+    }
+
+    // the allocated parts of the image in address order, without the
+    // padding past the end of the last page
+    pub fn chunks(&self) -> Vec<(usize, &[W])> {
+        match &self.backend {
+            Backend::Flat(cells) => vec![(0, &cells[..])],
+            Backend::Paged(pages) => {
+                let mut ids: Vec<&usize> = pages.keys().collect();
+                ids.sort();
+                ids.into_iter().map(|id| {
+                    let start = id * PAGE_SIZE;
+                    let len = usize::min(PAGE_SIZE, self.size - start);
+                    (start, &pages[id][.. len])
+                }).collect()
+            }
+        }
+    }
+
+    pub fn footprint(&self) -> Footprint {
+        match &self.backend {
+            Backend::Flat(cells) => Footprint {size: self.size, allocated: cells.capacity(), pages: 0},
+            Backend::Paged(pages) => Footprint {size: self.size, allocated: pages.len() * PAGE_SIZE, pages: pages.len()}
+        }
+    }
+}
+
+impl<W: Word> From<Vec<W>> for Memory<W> {
+    fn from(image: Vec<W>) -> Self {
+        Memory::flat(image)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn flat() {
+        let mut mem = Memory::flat(vec![1i64, 2, 3]);
+        assert_eq!(mem.get(1), 2);
+        assert_eq!(mem.get(10), 0);
+        assert_eq!(mem.len(), 3);
+
+        mem.set(5, 7);
+        assert_eq!(mem.to_vec(), vec![1, 2, 3, 0, 0, 7]);
+        assert!(!mem.is_paged());
+    }
+
+    #[test]
+    fn paged() {
+        let mut mem = Memory::paged(vec![1i64, 2, 3]);
+        assert_eq!(mem.get(2), 3);
+        assert_eq!(mem.get(5000), 0);
+        assert_eq!(mem.footprint().pages, 1);
+
+        mem.set(5000, 9);
+        assert_eq!(mem.get(5000), 9);
+        assert_eq!(mem.len(), 5001);
+        assert_eq!(mem.footprint(), Footprint {size: 5001, allocated: 2 * PAGE_SIZE, pages: 2});
+    }
+
+    #[test]
+    fn promote() {
+        let mut mem = Memory::flat(vec![1i64, 2, 3]);
+        mem.set(1 << 40, 4);
+        assert!(mem.is_paged());
+        assert_eq!(mem.get(0), 1);
+        assert_eq!(mem.get(1 << 40), 4);
+        assert_eq!(mem.footprint().pages, 2);
+    }
+
+    #[test]
+    fn truncate() {
+        let mut mem = Memory::flat(vec![1i64, 2, 3]);
+        mem.set(4, 5);
+        mem.truncate(2);
+        assert_eq!(mem.to_vec(), vec![1, 2]);
+
+        let mut mem = Memory::paged(vec![1i64, 2, 3]);
+        mem.set(5000, 9);
+        mem.truncate(2);
+        assert_eq!(mem.len(), 2);
+        assert_eq!(mem.get(2), 0);
+        assert_eq!(mem.get(5000), 0);
+        assert_eq!(mem.footprint().pages, 1);
+    }
+}
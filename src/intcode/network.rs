@@ -0,0 +1,226 @@
+use super::{IntCodePC, IntcodeError, State, Word};
+
+#[derive(Debug)]
+pub enum NodeState<W: Word = i64> {
+    Ready,
+    Blocked,
+    Halted(W),
+    Failed(IntcodeError<W>)
+}
+
+#[derive(Debug)]
+pub struct NodeReport<W: Word = i64> {
+    pub name: String,
+    pub state: NodeState<W>,
+    pub outputs: Vec<W>
+}
+
+#[derive(Debug)]
+pub struct Report<W: Word = i64> {
+    pub nodes: Vec<NodeReport<W>>,
+    pub sink: Vec<W>
+}
+
+struct Node<W: Word> {
+    name: String,
+    pc: IntCodePC<'static, 'static, W>,
+    state: NodeState<W>,
+    targets: Vec<usize>,
+    to_sink: bool,
+    outputs: Vec<W>
+}
+
+pub struct Network<W: Word = i64> {
+    nodes: Vec<Node<W>>,
+    sink: Vec<W>
+}
+
+impl<W: Word> Network<W> {
+
+    pub fn new() -> Network<W> {
+        Network {nodes: Vec::new(), sink: Vec::new()}
+    }
+
+    pub fn add_node(&mut self, name: &str, program: Vec<W>, inputs: Vec<W>) -> usize {
+        let mut pc = IntCodePC::new_detached(program);
+        for val in inputs {
+            pc.feed(val);
+        }
+
+        self.nodes.push(Node {
+            name: String::from(name),
+            pc,
+            state: NodeState::Ready,
+            targets: Vec::new(),
+            to_sink: false,
+            outputs: Vec::new()
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.name == name)
+    }
+
+    // every output of `from` is delivered to each node it is connected to,
+    // a node connected from several others receives their outputs interleaved
+    pub fn connect(&mut self, from: usize, to: usize) {
+        if !self.nodes[from].targets.contains(&to) {
+            self.nodes[from].targets.push(to);
+        }
+    }
+
+    pub fn broadcast(&mut self, from: usize) {
+        for to in (0 .. self.nodes.len()).filter(|&to| to != from) {
+            self.connect(from, to);
+        }
+    }
+
+    pub fn connect_sink(&mut self, from: usize) {
+        self.nodes[from].to_sink = true;
+    }
+
+    pub fn feed(&mut self, node: usize, val: W) {
+        self.nodes[node].pc.feed(val);
+    }
+
+    // runs the nodes round-robin until all of them are halted, failed, or
+    // blocked on input nobody is going to send
+    pub fn run(mut self) -> Report<W> {
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for id in 0 .. self.nodes.len() {
+                progress |= self.run_node(id);
+            }
+        }
+
+        let nodes = self.nodes.into_iter().map(|n| NodeReport {name: n.name, state: n.state, outputs: n.outputs}).collect();
+        Report {nodes, sink: self.sink}
+    }
+
+    fn run_node(&mut self, id: usize) -> bool {
+        match self.nodes[id].state {
+            NodeState::Halted(_) | NodeState::Failed(_) => return false,
+            NodeState::Blocked if self.nodes[id].pc.pending_input() == 0 => return false,
+            _ => {}
+        }
+
+        let mut progress = false;
+        loop {
+            let pending = self.nodes[id].pc.pending_input();
+            let state = self.nodes[id].pc.resume();
+            progress |= self.nodes[id].pc.pending_input() != pending;
+
+            match state {
+                Ok(State::Output(val)) => {
+                    progress = true;
+                    self.deliver(id, val);
+                },
+                Ok(State::NeedsInput) => {
+                    self.nodes[id].state = NodeState::Blocked;
+                    return progress;
+                },
+                Ok(State::Halted(val)) => {
+                    self.nodes[id].state = NodeState::Halted(val);
+                    return true;
+                },
+                Err(e) => {
+                    self.nodes[id].state = NodeState::Failed(e);
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self, from: usize, val: W) {
+        for to in self.nodes[from].targets.clone() {
+            self.nodes[to].pc.feed(val.clone());
+        }
+        if self.nodes[from].to_sink {
+            self.sink.push(val.clone());
+        }
+        self.nodes[from].outputs.push(val);
+    }
+}
+
+impl<W: Word> Default for Network<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // reads one value, writes it doubled, forever
+    fn doubler() -> Vec<i64> {
+        vec![3,11,1002,11,2,11,4,11,1105,1,0,0]
+    }
+
+    // reads two values, writes their sum, halts
+    fn adder() -> Vec<i64> {
+        vec![3,11,3,12,1,11,12,13,4,13,99,0,0,0]
+    }
+
+    #[test]
+    fn ring() {
+        let prog = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        let mut net = Network::new();
+        let ids: Vec<_> = [9, 8, 7, 6, 5].iter().map(|&p| net.add_node("amp", prog.clone(), vec![p])).collect();
+        for n in 0 .. ids.len() {
+            net.connect(ids[n], ids[(n + 1) % ids.len()]);
+        }
+        net.connect_sink(ids[4]);
+        net.feed(ids[0], 0);
+
+        let report = net.run();
+        assert_eq!(report.sink.last(), Some(&139629729));
+        assert!(report.nodes.iter().all(|n| matches!(n.state, NodeState::Halted(_))));
+    }
+
+    #[test]
+    fn fan_out_fan_in() {
+        let mut net = Network::new();
+        let src = net.add_node("src", vec![104,5,99], vec![]);
+        let left = net.add_node("left", doubler(), vec![]);
+        let right = net.add_node("right", doubler(), vec![]);
+        let sum = net.add_node("sum", adder(), vec![]);
+        net.connect(src, left);
+        net.connect(src, right);
+        net.connect(left, sum);
+        net.connect(right, sum);
+        net.connect_sink(sum);
+
+        let report = net.run();
+        assert_eq!(report.sink, vec![20]);
+        assert_eq!(report.nodes[left].outputs, vec![10]);
+        assert!(matches!(report.nodes[left].state, NodeState::Blocked));
+        assert!(matches!(report.nodes[sum].state, NodeState::Halted(3)));
+    }
+
+    #[test]
+    fn broadcast() {
+        let mut net = Network::new();
+        let src = net.add_node("src", vec![104,7,104,1,99], vec![]);
+        net.add_node("a", adder(), vec![]);
+        net.add_node("b", adder(), vec![]);
+        net.broadcast(src);
+        net.connect_sink(net.find_node("a").unwrap());
+        net.connect_sink(net.find_node("b").unwrap());
+
+        let report = net.run();
+        assert_eq!(report.sink, vec![8, 8]);
+    }
+
+    #[test]
+    fn failure() {
+        let mut net = Network::new();
+        net.add_node("bad", vec![104,1,42], vec![]);
+        let report = net.run();
+        assert!(matches!(report.nodes[0].state, NodeState::Failed(IntcodeError::UnknownOpcode {pc: 2, ins: 42})));
+        assert_eq!(report.nodes[0].outputs, vec![1]);
+    }
+}
@@ -0,0 +1,318 @@
+use super::{Memory, Word, INSTRUCTIONS};
+use super::disasm::{decode_in, label};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+const ROOT: usize = 0;
+
+// counts past this address go in a map, so that a jump into sparse memory
+// does not size the dense counts after it
+const MAX_DENSE: usize = 1 << 20;
+
+// A call is taken to be a jump to an immediate target right after an
+// instruction that wrote the address following the jump, i.e. pushed a
+// return address; a return is a jump through memory back to the return
+// address of a frame on the stack. Good enough for compiled Intcode.
+struct Node {
+    func: usize,
+    parent: usize,
+    samples: u64
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Branch {
+    pub executed: u64,
+    pub taken: u64
+}
+
+pub struct Profile {
+    total: u64,
+    counts: Vec<u64>,
+    sparse: HashMap<usize, u64>,
+    opcodes: BTreeMap<i64, u64>,
+    branches: BTreeMap<usize, Branch>,
+    loops: HashMap<(usize, usize), u64>,
+    nodes: Vec<Node>,
+    children: HashMap<(usize, usize), usize>,
+    stack: Vec<(usize, usize)>,
+    node: usize,
+    written: Option<i64>,
+    last_written: Option<i64>
+}
+
+impl Profile {
+
+    pub fn new() -> Profile {
+        Profile {
+            total: 0,
+            counts: Vec::new(),
+            sparse: HashMap::new(),
+            opcodes: BTreeMap::new(),
+            branches: BTreeMap::new(),
+            loops: HashMap::new(),
+            nodes: vec![Node {func: 0, parent: ROOT, samples: 0}],
+            children: HashMap::new(),
+            stack: Vec::new(),
+            node: ROOT,
+            written: None,
+            last_written: None
+        }
+    }
+
+    // called for each value an instruction stores, before step()
+    pub fn wrote(&mut self, val: Option<i64>) {
+        self.written = val;
+    }
+
+    // called after each executed instruction with the pc it left behind
+    pub fn step(&mut self, pc: usize, ins: i64, next: usize) {
+        self.total += 1;
+        if pc >= MAX_DENSE {
+            *self.sparse.entry(pc).or_insert(0) += 1;
+        } else {
+            if pc >= self.counts.len() {
+                self.counts.resize(pc + 1, 0);
+            }
+            self.counts[pc] += 1;
+        }
+        *self.opcodes.entry(ins % 100).or_insert(0) += 1;
+        self.nodes[self.node].samples += 1;
+
+        if ins % 100 == 5 || ins % 100 == 6 {
+            let branch = self.branches.entry(pc).or_default();
+            branch.executed += 1;
+            if next != pc + 3 {
+                branch.taken += 1;
+                self.jumped(pc, ins, next);
+            }
+        }
+
+        self.last_written = self.written.take();
+    }
+
+    // backward jumps other than calls and returns close loops
+    fn jumped(&mut self, pc: usize, ins: i64, next: usize) {
+        let immediate = (ins / 1000) % 10 == 1;
+        let ret = if immediate { None } else { self.stack.iter().rposition(|&(_, ret)| ret == next) };
+
+        if immediate && self.last_written == Some(pc as i64 + 3) {
+            self.stack.push((self.node, pc + 3));
+            let parent = self.node;
+            let len = self.nodes.len();
+            self.node = *self.children.entry((parent, next)).or_insert(len);
+            if self.node == len {
+                self.nodes.push(Node {func: next, parent, samples: 0});
+            }
+        } else if let Some(at) = ret {
+            self.node = self.stack[at].0;
+            self.stack.truncate(at);
+        } else if next <= pc {
+            *self.loops.entry((pc, next)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, addr: usize) -> u64 {
+        match self.counts.get(addr) {
+            Some(&n) => n,
+            None => self.sparse.get(&addr).cloned().unwrap_or(0)
+        }
+    }
+
+    pub fn opcode_count(&self, opcode: i64) -> u64 {
+        self.opcodes.get(&opcode).cloned().unwrap_or(0)
+    }
+
+    pub fn branch(&self, addr: usize) -> Option<Branch> {
+        self.branches.get(&addr).cloned()
+    }
+
+    // executed addresses, hottest first
+    pub fn hot(&self) -> Vec<(usize, u64)> {
+        let mut hot: Vec<(usize, u64)> = self.counts.iter().cloned().enumerate().filter(|&(_, n)| n > 0)
+            .chain(self.sparse.iter().map(|(&addr, &n)| (addr, n)))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot
+    }
+
+    // taken backward jumps as (start, end, iterations, instructions in the
+    // body), most iterations first
+    pub fn loops(&self) -> Vec<(usize, usize, u64, u64)> {
+        let mut loops: Vec<(usize, usize, u64, u64)> = self.loops.iter().map(|(&(from, to), &n)| {
+            let dense: u64 = self.counts.iter().take(from + 1).skip(to).sum();
+            let body = dense + self.sparse.iter().filter(|(&addr, _)| to <= addr && addr <= from).map(|(_, &n)| n).sum::<u64>();
+            (to, from, n, body)
+        }).collect();
+        loops.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        loops
+    }
+
+    // one "main;l100;l250 count" line per call path, for flamegraph.pl
+    pub fn folded(&self) -> String {
+        let mut lines = Vec::new();
+        for (id, node) in self.nodes.iter().enumerate() {
+            if node.samples == 0 {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut at = id;
+            while at != ROOT {
+                path.push(label(self.nodes[at].func));
+                at = self.nodes[at].parent;
+            }
+            path.push(String::from("main"));
+            path.reverse();
+            lines.push(format!("{} {}", path.join(";"), node.samples));
+        }
+        lines.sort();
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+
+    pub fn report<W: Word>(&self, memory: &Memory<W>, top: usize) -> String {
+        let pct = |n: u64| 100.0 * n as f64 / self.total.max(1) as f64;
+        let render = |addr: usize| decode_in(memory, addr).map(|ins| ins.render(&Default::default())).unwrap_or_else(|| String::from("?"));
+        let mut out = String::new();
+
+        writeln!(out, "{} instructions executed", self.total).unwrap();
+
+        writeln!(out, "\nhot addresses").unwrap();
+        for (addr, n) in self.hot().into_iter().take(top) {
+            writeln!(out, "{:>14} {:>6.2}%  {:04}: {}", n, pct(n), addr, render(addr)).unwrap();
+        }
+
+        writeln!(out, "\nopcodes").unwrap();
+        let mut opcodes: Vec<(&i64, &u64)> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1));
+        for (op, &n) in opcodes {
+            let mnemonic = INSTRUCTIONS.iter().find(|(code, _, _)| code == op).map(|i| i.1).unwrap_or("?");
+            writeln!(out, "{:>14} {:>6.2}%  {}", n, pct(n), mnemonic).unwrap();
+        }
+
+        writeln!(out, "\nbranches").unwrap();
+        let mut branches: Vec<(&usize, &Branch)> = self.branches.iter().collect();
+        branches.sort_by(|a, b| b.1.executed.cmp(&a.1.executed).then(a.0.cmp(b.0)));
+        for (addr, b) in branches.into_iter().take(top) {
+            let taken = 100.0 * b.taken as f64 / b.executed as f64;
+            writeln!(out, "{:>14} {:>6.2}% taken  {:04}: {}", b.executed, taken, addr, render(*addr)).unwrap();
+        }
+
+        writeln!(out, "\nhot loops").unwrap();
+        for (start, end, n, body) in self.loops().into_iter().take(top) {
+            writeln!(out, "{:>14} {:>6.2}%  {:04}..{:04}, {} iterations", body, pct(body), start, end, n).unwrap();
+        }
+        out
+    }
+
+    // the executed instructions in address order with their counts, runs
+    // of memory that never ran collapsed to a single line
+    pub fn annotate<W: Word>(&self, memory: &Memory<W>) -> String {
+        let mut executed: Vec<usize> = self.hot().into_iter().map(|(addr, _)| addr).collect();
+        executed.sort();
+
+        let mut out = String::new();
+        let mut end = 0;
+        for addr in executed {
+            let ins = match decode_in(memory, addr) {
+                Some(ins) => ins,
+                None => continue
+            };
+            if addr > end {
+                writeln!(out, "{:>14}  ...", "").unwrap();
+            }
+            let raw: Vec<String> = (addr .. addr + ins.width()).map(|a| memory.get(a).to_string()).collect();
+            writeln!(out, "{:>14}  {:04}: {:<32}; {}", self.count(addr), addr, ins.render(&Default::default()), raw.join(" ")).unwrap();
+            end = end.max(addr + ins.width());
+        }
+        if end < memory.len() {
+            writeln!(out, "{:>14}  ...", "").unwrap();
+        }
+        out
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::new()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::IntCodePC;
+
+    // sums 3 + 2 + 1 by calling a function that adds [27] to [28] and
+    // counts [27] down, three times
+    //
+    //      0: arb #100
+    //      2: add #9, #0, rb+0         push return address 9
+    //      6: jnz #1, #14              call
+    //      9: jnz [27], #2             loop while [27] != 0
+    //     12: halt
+    //     14: add [27], [28], [28]
+    //     18: add [27], #-1, [27]
+    //     22: jz #0, rb+0              return
+    const PROG: [i64; 29] = [109,100,21101,9,0,0,1105,1,14,1005,27,2,99,0,1,27,28,28,1001,27,-1,27,2106,0,0,0,0,3,0];
+
+    fn profile() -> Profile {
+        let mut pc = IntCodePC::new_detached(PROG.to_vec());
+        pc.enable_profile();
+        pc.run().unwrap();
+        assert_eq!(pc.memory().get(28), 6);
+        pc.take_profile().unwrap()
+    }
+
+    #[test]
+    fn counts() {
+        let p = profile();
+        assert_eq!(p.total(), 1 + 3 * 6 + 1);
+        assert_eq!(p.count(0), 1);
+        assert_eq!(p.count(14), 3);
+        assert_eq!(p.opcode_count(1), 9);
+        assert_eq!(p.branch(9), Some(Branch {executed: 3, taken: 2}));
+        assert_eq!(p.branch(6), Some(Branch {executed: 3, taken: 3}));
+        assert_eq!(p.hot()[0], (2, 3));
+        assert_eq!(p.loops(), vec![(2, 9, 2, 9)]);
+    }
+
+    #[test]
+    fn folded() {
+        let p = profile();
+        assert_eq!(p.folded(), "main 11\nmain;l14 9\n");
+    }
+
+    #[test]
+    fn annotate() {
+        let p = profile();
+        let memory = Memory::from(PROG.to_vec());
+        let text = p.annotate(&memory);
+        assert!(text.starts_with("             1  0000: arb #100                        ; 109 100\n"), "{}", text);
+        assert!(text.contains("\n                ...\n             3  0014: add [27], [28], [28]"), "{}", text);
+        assert!(text.ends_with("0022: jz #0, rb+0                     ; 2106 0 0\n                ...\n"), "{}", text);
+        assert!(p.report(&memory, 3).contains("  0002..0009, 2 iterations"));
+    }
+
+    #[test]
+    fn sparse() {
+        // jumps far out into paged memory and halts there
+        let far = 1 << 40;
+        let mut pc = IntCodePC::new_detached(vec![1105, 1, far as i64]);
+        pc.memory_mut().set(far, 99);
+        pc.enable_profile();
+        pc.run().unwrap();
+        let p = pc.take_profile().unwrap();
+        assert_eq!(p.count(far), 1);
+        assert_eq!(p.hot(), vec![(0, 1), (far, 1)]);
+        assert!(p.counts.len() <= 1);
+
+        let text = p.annotate(pc.memory());
+        assert_eq!(text, "             1  0000: jnz #1, #1099511627776          ; 1105 1 1099511627776\n\
+                         \x20               ...\n\
+                         \x20            1  1099511627776: halt                            ; 99\n");
+    }
+}
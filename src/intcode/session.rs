@@ -0,0 +1,257 @@
+use super::{Record, TraceSink, Word};
+use std::fmt;
+use std::io::{self, Error};
+
+// A session log has one line per I/O event, with the number of instructions
+// executed before the instruction that did it:
+//
+//     in 0 5
+//     out 6 42
+//
+// Blank lines and lines starting with # are ignored.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event<W: Word = i64> {
+    Input(W),
+    Output(W)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry<W: Word = i64> {
+    pub at: u64,
+    pub event: Event<W>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Session<W: Word = i64> {
+    pub entries: Vec<Entry<W>>
+}
+
+impl<W: Word> fmt::Display for Entry<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.event {
+            Event::Input(val) => write!(f, "in {} {}", self.at, val),
+            Event::Output(val) => write!(f, "out {} {}", self.at, val)
+        }
+    }
+}
+
+impl<W: Word> Session<W> {
+
+    pub fn new() -> Session<W> {
+        Session {entries: Vec::new()}
+    }
+
+    pub fn parse(text: &str) -> Result<Session<W>, String> {
+        let mut entries = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = || format!("line {}: expected in|out <count> <value>, got {:?}", n + 1, line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (kind, at, val) = match words.as_slice() {
+                [kind, at, val] => (*kind, at.parse().map_err(|_| err())?, W::parse(val).map_err(|_| err())?),
+                _ => return Err(err())
+            };
+            let event = match kind {
+                "in" => Event::Input(val),
+                "out" => Event::Output(val),
+                _ => return Err(err())
+            };
+            entries.push(Entry {at, event});
+        }
+        Ok(Session {entries})
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = W> + '_ {
+        self.entries.iter().filter_map(|e| match &e.event {
+            Event::Input(val) => Some(val.clone()),
+            Event::Output(_) => None
+        })
+    }
+}
+
+impl<W: Word> Default for Session<W> {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl<W: Word> fmt::Display for Session<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+fn event<W: Word>(at: u64, rec: &Record<W>) -> Option<Entry<W>> {
+    match (&rec.input, &rec.output) {
+        (Some(val), _) => Some(Entry {at, event: Event::Input(val.clone())}),
+        (_, Some(val)) => Some(Entry {at, event: Event::Output(val.clone())}),
+        _ => None
+    }
+}
+
+// collects the I/O of a run, installed as the machine's trace sink
+pub struct Recorder<W: Word = i64> {
+    count: u64,
+    pub session: Session<W>
+}
+
+impl<W: Word> Recorder<W> {
+    pub fn new() -> Recorder<W> {
+        Recorder {count: 0, session: Session::new()}
+    }
+}
+
+impl<W: Word> Default for Recorder<W> {
+    fn default() -> Self {
+        Recorder::new()
+    }
+}
+
+impl<W: Word> TraceSink<W> for Recorder<W> {
+    fn record(&mut self, rec: &Record<W>) -> io::Result<()> {
+        if let Some(entry) = event(self.count, rec) {
+            self.session.entries.push(entry);
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+// the first point where a replay and its recording part ways; None on
+// either side means that side had no more events
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mismatch<W: Word = i64> {
+    pub expected: Option<Entry<W>>,
+    pub actual: Option<Entry<W>>
+}
+
+impl<W: Word> fmt::Display for Mismatch<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.expected, &self.actual) {
+            (Some(e), Some(a)) => write!(f, "expected {}, got {}", e, a),
+            (Some(e), None) => write!(f, "expected {}, but the run ended", e),
+            (None, Some(a)) => write!(f, "got {} past the end of the recording", a),
+            (None, None) => write!(f, "no mismatch")
+        }
+    }
+}
+
+// checks a run against a recording, installed as the machine's trace sink;
+// the recorded inputs have to be fed to the machine separately
+pub struct Replayer<W: Word = i64> {
+    count: u64,
+    next: usize,
+    session: Session<W>,
+    mismatch: Option<Mismatch<W>>
+}
+
+impl<W: Word> Replayer<W> {
+
+    pub fn new(session: Session<W>) -> Replayer<W> {
+        Replayer {count: 0, next: 0, session, mismatch: None}
+    }
+
+    pub fn matched(&self) -> usize {
+        self.next
+    }
+
+    // the first mismatch, counting recorded events the run never got to
+    pub fn finish(self) -> Result<usize, Mismatch<W>> {
+        if let Some(mismatch) = self.mismatch {
+            return Err(mismatch);
+        }
+        match self.session.entries.get(self.next) {
+            Some(expected) => Err(Mismatch {expected: Some(expected.clone()), actual: None}),
+            None => Ok(self.next)
+        }
+    }
+}
+
+impl<W: Word> TraceSink<W> for Replayer<W> {
+    fn record(&mut self, rec: &Record<W>) -> io::Result<()> {
+        let actual = event(self.count, rec);
+        self.count += 1;
+        if actual.is_none() || self.mismatch.is_some() {
+            return Ok(());
+        }
+
+        let expected = self.session.entries.get(self.next);
+        if expected == actual.as_ref() {
+            self.next += 1;
+            return Ok(());
+        }
+
+        let mismatch = Mismatch {expected: expected.cloned(), actual};
+        let error = Error::other(format!("replay diverged: {}", mismatch));
+        self.mismatch = Some(mismatch);
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::IntCodePC;
+
+    // echoes the sum of each pair of inputs until it reads a zero
+    const PROG: [i64; 20] = [3,18,1006,18,17,3,19,1,18,19,19,4,19,1105,1,0,0,99,0,0];
+
+    fn record(inputs: Vec<i64>) -> Session {
+        let mut recorder = Recorder::new();
+        let mut pc = IntCodePC::new_detached(PROG.to_vec());
+        inputs.into_iter().for_each(|v| pc.feed(v));
+        pc.trace_to(&mut recorder);
+        pc.run().unwrap();
+        drop(pc);
+        recorder.session
+    }
+
+    fn replay(prog: Vec<i64>, session: &Session) -> Result<usize, Mismatch> {
+        let mut replayer = Replayer::new(session.clone());
+        let mut pc = IntCodePC::new_detached(prog);
+        session.inputs().for_each(|v| pc.feed(v));
+        pc.trace_to(&mut replayer);
+        let _ = pc.run();
+        drop(pc);
+        replayer.finish()
+    }
+
+    #[test]
+    fn log_format() {
+        let session = record(vec![2, 3, 0]);
+        assert_eq!(session.to_string(), "in 0 2\nin 2 3\nout 4 5\nin 6 0\n");
+        assert_eq!(Session::parse("# header\n\nin 0 2\nin 2 3\nout 4 5\nin 6 0\n").unwrap(), session);
+        assert_eq!(Session::<i64>::parse("in 0").unwrap_err(), "line 1: expected in|out <count> <value>, got \"in 0\"");
+        assert!(Session::<i64>::parse("put 0 1").is_err());
+    }
+
+    #[test]
+    fn replays() {
+        let session = record(vec![2, 3, 10, -4, 0]);
+        assert_eq!(replay(PROG.to_vec(), &session), Ok(7));
+
+        // a program that adds one too many diverges at the first output
+        let mut wrong = PROG.to_vec();
+        wrong[7] = 1101;
+        wrong[8] = 1;
+        wrong[9] = 5;
+        let mismatch = replay(wrong, &session).unwrap_err();
+        assert_eq!(mismatch.to_string(), "expected out 4 5, got out 4 6");
+
+        // and one that stops early leaves recorded events unmatched
+        let mut early = PROG.to_vec();
+        early[13] = 99;
+        let mismatch = replay(early, &session).unwrap_err();
+        assert_eq!(mismatch.expected, Some(Entry {at: 6, event: Event::Input(10)}));
+        assert_eq!(mismatch.actual, None);
+    }
+}
@@ -0,0 +1,286 @@
+use super::{Memory, ParamMode, Word};
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::convert::TryFrom;
+
+// File layout, integers little endian, words as a u32 length followed by
+// their decimal digits so that any Word type round trips:
+//
+//     "ICVM" version:u8
+//     pc:u64 ins_pc:u64 ins:word rb:word
+//     modes:u32 mode:u8...
+//     queue:u32 word...
+//     yielded:u8 [word]
+//     paged:u8 size:u64 chunks:u32 (start:u64 len:u32 word...)...
+const MAGIC: &[u8; 4] = b"ICVM";
+const VERSION: u8 = 2;
+
+// everything IntCodePC needs to carry on from where it was taken; the I/O
+// ports are not part of it, a restored machine uses whatever it was built with
+#[derive(Clone, Debug)]
+pub struct Snapshot<W: Word = i64> {
+    pub memory: Memory<W>,
+    pub pc: usize,
+    pub rb: W,
+    pub ins_pc: usize,
+    pub ins: W,
+    pub modes: Vec<ParamMode>,
+    pub queue: Vec<W>,
+    pub yielded: Option<W>
+}
+
+impl<W: Word> Snapshot<W> {
+
+    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_u64(w, self.pc)?;
+        write_u64(w, self.ins_pc)?;
+        write_word(w, &self.ins)?;
+        write_word(w, &self.rb)?;
+
+        write_u32(w, self.modes.len())?;
+        for mode in self.modes.iter() {
+            w.write_all(&[match mode {
+                ParamMode::Position => 0,
+                ParamMode::Immediate => 1,
+                ParamMode::Relative => 2
+            }])?;
+        }
+
+        write_u32(w, self.queue.len())?;
+        for val in self.queue.iter() {
+            write_word(w, val)?;
+        }
+
+        match &self.yielded {
+            Some(val) => {
+                w.write_all(&[1])?;
+                write_word(w, val)?;
+            },
+            None => w.write_all(&[0])?
+        }
+
+        let chunks = self.memory.chunks();
+        w.write_all(&[self.memory.is_paged() as u8])?;
+        write_u64(w, self.memory.len())?;
+        write_u32(w, chunks.len())?;
+        for (start, words) in chunks {
+            write_u64(w, start)?;
+            write_u32(w, words.len())?;
+            for val in words.iter() {
+                write_word(w, val)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from(r: &mut dyn Read) -> io::Result<Snapshot<W>> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an intcode snapshot"));
+        }
+        if read_u8(r)? != VERSION {
+            return Err(invalid("unsupported snapshot version"));
+        }
+
+        let pc = read_u64(r)?;
+        let ins_pc = read_u64(r)?;
+        let ins = read_word(r)?;
+        let rb = read_word(r)?;
+
+        let mut modes = Vec::new();
+        for _ in 0 .. read_u32(r)? {
+            modes.push(match read_u8(r)? {
+                0 => ParamMode::Position,
+                1 => ParamMode::Immediate,
+                2 => ParamMode::Relative,
+                _ => return Err(invalid("bad parameter mode"))
+            });
+        }
+
+        let mut queue = Vec::new();
+        for _ in 0 .. read_u32(r)? {
+            queue.push(read_word(r)?);
+        }
+
+        let yielded = match read_u8(r)? {
+            0 => None,
+            _ => Some(read_word(r)?)
+        };
+
+        let paged = read_u8(r)? != 0;
+        let size = read_u64(r)?;
+        let mut image = Vec::new();
+        let mut memory = Memory::paged(Vec::new());
+        for _ in 0 .. read_u32(r)? {
+            let start = read_u64(r)?;
+            let len = read_u32(r)?;
+            let end = start.checked_add(len).ok_or_else(|| invalid("memory chunk runs past the end of the address space"))?;
+            if !paged && start != image.len() {
+                return Err(invalid("flat memory chunk out of place"));
+            }
+            for addr in start .. end {
+                let val = read_word(r)?;
+                if paged {
+                    memory.set(addr, val);
+                } else {
+                    image.push(val);
+                }
+            }
+        }
+        if !paged {
+            memory = Memory::flat(image);
+        }
+        if memory.len() != size {
+            return Err(invalid("memory size does not match its contents"));
+        }
+
+        Ok(Snapshot {memory, pc, rb, ins_pc, ins, modes, queue, yielded})
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_u32(w: &mut dyn Write, val: usize) -> io::Result<()> {
+    let val = u32::try_from(val).map_err(|_| Error::new(ErrorKind::InvalidInput, "too large for the snapshot format"))?;
+    w.write_all(&val.to_le_bytes())
+}
+
+fn write_u64(w: &mut dyn Write, val: usize) -> io::Result<()> {
+    w.write_all(&(val as u64).to_le_bytes())
+}
+
+fn write_word<W: Word>(w: &mut dyn Write, val: &W) -> io::Result<()> {
+    let text = val.to_string();
+    write_u32(w, text.len())?;
+    w.write_all(text.as_bytes())
+}
+
+fn read_u8(r: &mut dyn Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(r: &mut dyn Read) -> io::Result<usize> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf) as usize)
+}
+
+fn read_u64(r: &mut dyn Read) -> io::Result<usize> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf) as usize)
+}
+
+fn read_word<W: Word>(r: &mut dyn Read) -> io::Result<W> {
+    // read through take so a corrupt length cannot allocate gigabytes up front
+    let len = read_u32(r)?;
+    let mut buf = Vec::new();
+    r.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "word cut short"));
+    }
+    let text = String::from_utf8(buf).map_err(|_| invalid("bad word"))?;
+    W::parse(&text).map_err(|e| invalid(&e))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::{IntCodePC, State};
+
+    #[test]
+    fn restore() {
+        // out = in1 + in2
+        let prog = vec![3,11,3,12,1,11,12,13,4,13,99,0,0,0];
+        let mut pc = IntCodePC::new_detached(prog);
+        pc.feed(40);
+        assert_eq!(pc.resume().unwrap(), State::NeedsInput);
+        let snap = pc.snapshot();
+
+        pc.feed(2);
+        assert_eq!(pc.resume().unwrap(), State::Output(42));
+        pc.restore(&snap);
+        pc.feed(-40);
+        assert_eq!(pc.resume().unwrap(), State::Output(0));
+        assert_eq!(pc.resume().unwrap(), State::Halted(3));
+    }
+
+    #[test]
+    fn file_format() {
+        let mut pc = IntCodePC::new_detached(vec![109,7,3,0,99]);
+        pc.feed(5);
+        pc.feed(6);
+        pc.step().unwrap();
+        pc.memory_mut().set(200_000, -9);
+        assert!(pc.memory().is_paged());
+
+        let mut buf = Vec::new();
+        pc.snapshot().write_to(&mut buf).unwrap();
+        let snap: Snapshot = Snapshot::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(snap.pc, 2);
+        assert_eq!(snap.rb, 7);
+        assert_eq!(snap.queue, vec![5, 6]);
+        assert!(snap.memory.is_paged());
+        assert_eq!(snap.memory.len(), 200_001);
+        assert_eq!(snap.memory.get(200_000), -9);
+        assert_eq!(snap.memory.get(1), 7);
+
+        let mut flat = Vec::new();
+        IntCodePC::new_detached(vec![1i64, 2, 0, 0]).snapshot().write_to(&mut flat).unwrap();
+        let snap: Snapshot = Snapshot::read_from(&mut &flat[..]).unwrap();
+        assert_eq!(snap.memory.to_vec(), vec![1, 2, 0, 0]);
+
+        assert_eq!(Snapshot::<i64>::read_from(&mut &b"ICVX"[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(Snapshot::<i64>::read_from(&mut &buf[.. 20]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        assert_eq!(write_u32(&mut Vec::new(), u32::MAX as usize + 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn corrupt_chunks() {
+        // a one word memory, flat or paged, its only chunk moved to `start`
+        let write = |paged: bool, start: u64| {
+            let mut snap = IntCodePC::new_detached(vec![5i64]).snapshot();
+            if paged {
+                snap.memory = Memory::paged(vec![5]);
+            }
+            let mut buf = Vec::new();
+            snap.write_to(&mut buf).unwrap();
+            let at = buf.len() - 5 - 12;
+            buf[at .. at + 8].copy_from_slice(&start.to_le_bytes());
+            buf
+        };
+        let read = |buf: Vec<u8>| Snapshot::<i64>::read_from(&mut &buf[..]);
+
+        for &paged in [false, true].iter() {
+            let snap = read(write(paged, 0)).unwrap();
+            assert_eq!(snap.memory.is_paged(), paged);
+            assert_eq!(snap.memory.to_vec(), vec![5]);
+            assert_eq!(read(write(paged, u64::MAX)).unwrap_err().kind(), ErrorKind::InvalidData);
+            assert_eq!(read(write(paged, 3)).unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn long_words() {
+        use num_bigint::BigInt;
+
+        let big = <BigInt as Word>::parse(&"7".repeat(300)).unwrap();
+        let mut pc = IntCodePC::new_detached(vec![BigInt::from(99), big.clone()]);
+        pc.feed(-big.clone());
+
+        let mut buf = Vec::new();
+        pc.snapshot().write_to(&mut buf).unwrap();
+        let snap: Snapshot<BigInt> = Snapshot::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(snap.memory.get(1), big);
+        assert_eq!(snap.queue, vec![-big]);
+    }
+}
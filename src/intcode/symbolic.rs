@@ -0,0 +1,554 @@
+use super::{IntCodePC, State};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+// Runs a program on expressions instead of numbers. Control flow has to stay
+// concrete: a symbolic branch condition, jump target, relative base, opcode
+// or store address gets the run stuck. A load through a symbolic address
+// gives an Unknown, which is fine as long as it is overwritten or ignored;
+// Day 2 reads through its noun and verb once and throws the sum away.
+const MAX_STEPS: usize = 10_000_000;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr {
+    Const(i64),
+    Sym(usize),
+    Unknown,
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Le(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>)
+}
+
+// constant + sum of coefficient * symbol
+#[derive(Clone, PartialEq, Debug)]
+pub struct Linear {
+    pub constant: i64,
+    pub terms: BTreeMap<usize, i64>
+}
+
+impl Expr {
+
+    // the constructors fold constants, so a concrete computation stays a
+    // Const; None is an overflow
+    pub fn checked_add(lhs: Expr, rhs: Expr) -> Option<Expr> {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => a.checked_add(b).map(Expr::Const),
+            (Expr::Unknown, _) | (_, Expr::Unknown) => Some(Expr::Unknown),
+            (Expr::Const(0), e) | (e, Expr::Const(0)) => Some(e),
+            (a, b) => Some(Expr::Add(Box::new(a), Box::new(b)))
+        }
+    }
+
+    pub fn checked_mul(lhs: Expr, rhs: Expr) -> Option<Expr> {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => a.checked_mul(b).map(Expr::Const),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Some(Expr::Const(0)),
+            (Expr::Unknown, _) | (_, Expr::Unknown) => Some(Expr::Unknown),
+            (Expr::Const(1), e) | (e, Expr::Const(1)) => Some(e),
+            (a, b) => Some(Expr::Mul(Box::new(a), Box::new(b)))
+        }
+    }
+
+    pub fn le(lhs: Expr, rhs: Expr) -> Expr {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as i64),
+            (Expr::Unknown, _) | (_, Expr::Unknown) => Expr::Unknown,
+            (a, b) if a == b => Expr::Const(0),
+            (a, b) => Expr::Le(Box::new(a), Box::new(b))
+        }
+    }
+
+    pub fn eq(lhs: Expr, rhs: Expr) -> Expr {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as i64),
+            (Expr::Unknown, _) | (_, Expr::Unknown) => Expr::Unknown,
+            (a, b) if a == b => Expr::Const(1),
+            (a, b) => Expr::Eq(Box::new(a), Box::new(b))
+        }
+    }
+
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Expr::Const(val) => Some(*val),
+            _ => None
+        }
+    }
+
+    pub fn has_unknown(&self) -> bool {
+        match self {
+            Expr::Unknown => true,
+            Expr::Const(_) | Expr::Sym(_) => false,
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::Le(a, b) | Expr::Eq(a, b) => a.has_unknown() || b.has_unknown()
+        }
+    }
+
+    // the value for the given symbol values, None on overflow or if it
+    // depends on an Unknown
+    pub fn eval(&self, vals: &[i64]) -> Option<i64> {
+        match self {
+            Expr::Const(val) => Some(*val),
+            Expr::Sym(sym) => vals.get(*sym).cloned(),
+            Expr::Unknown => None,
+            Expr::Add(a, b) => a.eval(vals)?.checked_add(b.eval(vals)?),
+            Expr::Mul(a, b) => a.eval(vals)?.checked_mul(b.eval(vals)?),
+            Expr::Le(a, b) => Some((a.eval(vals)? < b.eval(vals)?) as i64),
+            Expr::Eq(a, b) => Some((a.eval(vals)? == b.eval(vals)?) as i64)
+        }
+    }
+
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(val) => Some(Linear {constant: *val, terms: BTreeMap::new()}),
+            Expr::Sym(sym) => Some(Linear {constant: 0, terms: vec![(*sym, 1)].into_iter().collect()}),
+            Expr::Add(a, b) => {
+                let (mut a, b) = (a.linear()?, b.linear()?);
+                a.constant = a.constant.checked_add(b.constant)?;
+                for (sym, k) in b.terms {
+                    let term = a.terms.entry(sym).or_insert(0);
+                    *term = term.checked_add(k)?;
+                }
+                a.terms.retain(|_, k| *k != 0);
+                Some(a)
+            },
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                let (k, mut e) = match (a.terms.is_empty(), b.terms.is_empty()) {
+                    (true, _) => (a.constant, b),
+                    (_, true) => (b.constant, a),
+                    _ => return None
+                };
+                e.constant = e.constant.checked_mul(k)?;
+                for term in e.terms.values_mut() {
+                    *term = term.checked_mul(k)?;
+                }
+                e.terms.retain(|_, k| *k != 0);
+                Some(e)
+            },
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(val) => write!(f, "{}", val),
+            Expr::Sym(sym) => write!(f, "s{}", sym),
+            Expr::Unknown => write!(f, "?"),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+            Expr::Le(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Eq(a, b) => write!(f, "({} == {})", a, b)
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Stuck {
+    SymbolicCode(usize),
+    SymbolicAddress(usize),
+    SymbolicBranch(usize),
+    InputExhausted(usize),
+    Invalid(usize),
+    Overflow(usize),
+    TooLong
+}
+
+impl fmt::Display for Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stuck::SymbolicCode(pc) => write!(f, "symbolic instruction at {}", pc),
+            Stuck::SymbolicAddress(pc) => write!(f, "store through a symbolic address at {}", pc),
+            Stuck::SymbolicBranch(pc) => write!(f, "symbolic branch at {}", pc),
+            Stuck::InputExhausted(pc) => write!(f, "input exhausted at {}", pc),
+            Stuck::Invalid(pc) => write!(f, "invalid instruction at {}", pc),
+            Stuck::Overflow(pc) => write!(f, "arithmetic overflow at {}", pc),
+            Stuck::TooLong => write!(f, "no halt after {} instructions", MAX_STEPS)
+        }
+    }
+}
+
+pub struct Symbolic {
+    memory: Vec<Expr>,
+    // stores past the image, which may be anywhere
+    beyond: BTreeMap<usize, Expr>,
+    pc: usize,
+    rb: i64,
+    ins_pc: usize,
+    queue: VecDeque<Expr>,
+    outputs: Vec<Expr>,
+    symbols: usize
+}
+
+impl Symbolic {
+
+    pub fn new(image: &[i64]) -> Symbolic {
+        Symbolic {
+            memory: image.iter().map(|&w| Expr::Const(w)).collect(),
+            beyond: BTreeMap::new(),
+            pc: 0,
+            rb: 0,
+            ins_pc: 0,
+            queue: VecDeque::new(),
+            outputs: Vec::new(),
+            symbols: 0
+        }
+    }
+
+    // a new symbol, numbered from 0
+    pub fn symbol(&mut self) -> Expr {
+        self.symbols += 1;
+        Expr::Sym(self.symbols - 1)
+    }
+
+    pub fn get(&self, addr: usize) -> Expr {
+        self.memory.get(addr).or_else(|| self.beyond.get(&addr)).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    pub fn set(&mut self, addr: usize, val: Expr) {
+        match self.memory.get_mut(addr) {
+            Some(cell) => *cell = val,
+            None => {
+                self.beyond.insert(addr, val);
+            }
+        }
+    }
+
+    pub fn feed(&mut self, val: Expr) {
+        self.queue.push_back(val);
+    }
+
+    pub fn outputs(&self) -> &[Expr] {
+        &self.outputs
+    }
+
+    // runs to the halt and returns what is left at address 0
+    pub fn run(&mut self) -> Result<Expr, Stuck> {
+        for _ in 0 .. MAX_STEPS {
+            if self.step()? {
+                return Ok(self.get(0));
+            }
+        }
+        Err(Stuck::TooLong)
+    }
+
+    fn word(&self, addr: usize) -> Result<i64, Stuck> {
+        self.get(addr).as_const().ok_or(Stuck::SymbolicCode(self.ins_pc))
+    }
+
+    fn address(&self, addr: i64) -> Result<usize, Stuck> {
+        if addr < 0 {
+            Err(Stuck::Invalid(self.ins_pc))
+        } else {
+            Ok(addr as usize)
+        }
+    }
+
+    fn param(&self, n: usize) -> (i64, Expr) {
+        let ins = self.get(self.ins_pc).as_const().unwrap_or(0);
+        let mode = (ins / [100, 1000, 10000][n]) % 10;
+        (mode, self.get(self.ins_pc + 1 + n))
+    }
+
+    fn read(&self, n: usize) -> Result<Expr, Stuck> {
+        match self.param(n) {
+            (1, val) => Ok(val),
+            (0, Expr::Const(addr)) => Ok(self.get(self.address(addr)?)),
+            (2, Expr::Const(offset)) => {
+                let addr = self.rb.checked_add(offset).ok_or(Stuck::Overflow(self.ins_pc))?;
+                Ok(self.get(self.address(addr)?))
+            },
+            (0, _) | (2, _) => Ok(Expr::Unknown),
+            _ => Err(Stuck::Invalid(self.ins_pc))
+        }
+    }
+
+    fn write(&mut self, n: usize, val: Expr) -> Result<(), Stuck> {
+        let addr = match self.param(n) {
+            (0, Expr::Const(addr)) => addr,
+            (2, Expr::Const(offset)) => self.rb.checked_add(offset).ok_or(Stuck::Overflow(self.ins_pc))?,
+            (0, _) | (2, _) => return Err(Stuck::SymbolicAddress(self.ins_pc)),
+            _ => return Err(Stuck::Invalid(self.ins_pc))
+        };
+        let addr = self.address(addr)?;
+        self.set(addr, val);
+        Ok(())
+    }
+
+    fn concrete(&self, n: usize) -> Result<i64, Stuck> {
+        self.read(n)?.as_const().ok_or(Stuck::SymbolicBranch(self.ins_pc))
+    }
+
+    // executes one instruction, true on a halt
+    fn step(&mut self) -> Result<bool, Stuck> {
+        self.ins_pc = self.pc;
+        let ins = self.word(self.pc)?;
+        let overflow = Stuck::Overflow(self.pc);
+        let len = match ins % 100 {
+            1 | 2 | 7 | 8 => 4,
+            5 | 6 => 3,
+            3 | 4 | 9 => 2,
+            99 => return Ok(true),
+            _ => return Err(Stuck::Invalid(self.pc))
+        };
+        self.pc += len;
+
+        match ins % 100 {
+            1 => self.write(2, Expr::checked_add(self.read(0)?, self.read(1)?).ok_or(overflow)?)?,
+            2 => self.write(2, Expr::checked_mul(self.read(0)?, self.read(1)?).ok_or(overflow)?)?,
+            3 => {
+                let val = self.queue.pop_front().ok_or(Stuck::InputExhausted(self.ins_pc))?;
+                self.write(0, val)?
+            },
+            4 => {
+                let val = self.read(0)?;
+                self.outputs.push(val);
+            },
+            5 | 6 => {
+                let cond = self.concrete(0)?;
+                let target = self.concrete(1)?;
+                if (cond != 0) == (ins % 100 == 5) {
+                    self.pc = self.address(target)?;
+                }
+            },
+            7 => self.write(2, Expr::le(self.read(0)?, self.read(1)?))?,
+            8 => self.write(2, Expr::eq(self.read(0)?, self.read(1)?))?,
+            _ => {
+                let offset = self.concrete(0)?;
+                self.rb = self.rb.checked_add(offset).ok_or(overflow)?;
+            }
+        }
+        Ok(false)
+    }
+}
+
+// the values of the symbols s0, s1, ... in their ranges that make `expr`
+// equal `target`, the first in lexicographic order; None if there are none,
+// or if `expr` depends on an Unknown
+pub fn solve(expr: &Expr, target: i64, ranges: &[RangeInclusive<i64>]) -> Option<Vec<i64>> {
+    let mut vals = Vec::new();
+    if let Some(lin) = expr.linear() {
+        let mut coeffs = vec![0; ranges.len()];
+        for (sym, k) in lin.terms {
+            *coeffs.get_mut(sym)? = k as i128;
+        }
+        let rem = target as i128 - lin.constant as i128;
+        return if solve_linear(&coeffs, ranges, rem, &mut vals) { Some(vals) } else { None };
+    }
+    if expr.has_unknown() {
+        return None;
+    }
+    if enumerate(ranges, &mut vals, &mut |vals| expr.eval(vals) == Some(target)) { Some(vals) } else { None }
+}
+
+// all but the last symbol are tried in order, the last follows from them
+fn solve_linear(coeffs: &[i128], ranges: &[RangeInclusive<i64>], rem: i128, vals: &mut Vec<i64>) -> bool {
+    let n = vals.len();
+    if n == ranges.len() {
+        return rem == 0;
+    }
+    if n + 1 == ranges.len() && coeffs[n] != 0 {
+        let x = rem / coeffs[n];
+        if rem % coeffs[n] == 0 && x >= *ranges[n].start() as i128 && x <= *ranges[n].end() as i128 {
+            vals.push(x as i64);
+            return true;
+        }
+        return false;
+    }
+    for x in ranges[n].clone() {
+        vals.push(x);
+        if solve_linear(coeffs, ranges, rem - coeffs[n] * x as i128, vals) {
+            return true;
+        }
+        vals.pop();
+    }
+    false
+}
+
+fn enumerate<F: FnMut(&[i64]) -> bool>(ranges: &[RangeInclusive<i64>], vals: &mut Vec<i64>, found: &mut F) -> bool {
+    let n = vals.len();
+    if n == ranges.len() {
+        return found(vals);
+    }
+    for x in ranges[n].clone() {
+        vals.push(x);
+        if enumerate(ranges, vals, found) {
+            return true;
+        }
+        vals.pop();
+    }
+    false
+}
+
+// what a symbol stands for
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Source {
+    Cell(usize),
+    Input
+}
+
+// what has to come out equal to the target
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Goal {
+    Memory(usize),
+    Output(usize)
+}
+
+// the first values of `sources` in their ranges for which the program halts
+// with `goal` equal to `target`. Solved symbolically where the run allows
+// it, and then checked with a concrete run, which might fail where the
+// symbolic one skipped over a load through a bad address; else searched.
+pub fn find(image: &[i64], sources: &[Source], ranges: &[RangeInclusive<i64>], goal: Goal, target: i64) -> Option<Vec<i64>> {
+    let mut sym = Symbolic::new(image);
+    for source in sources {
+        let val = sym.symbol();
+        match source {
+            Source::Cell(addr) => sym.set(*addr, val),
+            Source::Input => sym.feed(val)
+        }
+    }
+
+    let expr = sym.run().ok().and_then(|_| match goal {
+        Goal::Memory(addr) => Some(sym.get(addr)),
+        Goal::Output(n) => sym.outputs().get(n).cloned()
+    });
+    if let Some(expr) = expr.filter(|e| !e.has_unknown()) {
+        match solve(&expr, target, ranges) {
+            Some(vals) if check(image, sources, &vals, goal, target) => return Some(vals),
+            None => return None,
+            _ => {}
+        }
+    }
+
+    let mut vals = Vec::new();
+    if enumerate(ranges, &mut vals, &mut |vals| check(image, sources, vals, goal, target)) { Some(vals) } else { None }
+}
+
+fn check(image: &[i64], sources: &[Source], vals: &[i64], goal: Goal, target: i64) -> bool {
+    let mut pc = IntCodePC::new_detached(image.to_vec());
+    for (source, val) in sources.iter().zip(vals) {
+        match source {
+            Source::Cell(addr) => pc.memory_mut().set(*addr, *val),
+            Source::Input => pc.feed(*val)
+        }
+    }
+    let mut outputs = Vec::new();
+    loop {
+        match pc.resume() {
+            Ok(State::Output(val)) => outputs.push(val),
+            Ok(State::Halted(_)) => break,
+            _ => return false
+        }
+    }
+    match goal {
+        Goal::Memory(addr) => pc.memory().get(addr) == target,
+        Goal::Output(n) => outputs.get(n) == Some(&target)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    fn brute(image: &[i64], sources: &[Source], ranges: &[RangeInclusive<i64>], goal: Goal, target: i64) -> Option<Vec<i64>> {
+        let mut vals = Vec::new();
+        if enumerate(ranges, &mut vals, &mut |vals| check(image, sources, vals, goal, target)) { Some(vals) } else { None }
+    }
+
+    #[test]
+    fn folding() {
+        let x = Expr::Sym(0);
+        assert_eq!(Expr::checked_add(Expr::Const(2), Expr::Const(3)), Some(Expr::Const(5)));
+        assert_eq!(Expr::checked_add(Expr::Const(i64::MAX), Expr::Const(1)), None);
+        assert_eq!(Expr::checked_mul(x.clone(), Expr::Const(1)), Some(x.clone()));
+        assert_eq!(Expr::checked_mul(Expr::Unknown, Expr::Const(0)), Some(Expr::Const(0)));
+        assert_eq!(Expr::eq(x.clone(), x.clone()), Expr::Const(1));
+
+        let e = Expr::checked_add(Expr::checked_mul(x, Expr::Const(3)).unwrap(), Expr::Sym(1)).unwrap();
+        assert_eq!(e.to_string(), "(s0 * 3 + s1)");
+        assert_eq!(e.eval(&[2, 5]), Some(11));
+        assert_eq!(e.linear(), Some(Linear {constant: 0, terms: vec![(0, 3), (1, 1)].into_iter().collect()}));
+    }
+
+    // 300 * noun + verb + 7 at address 0, after a sum through noun and verb
+    // as addresses that is overwritten, like Day 2
+    #[test]
+    fn linear() {
+        let prog = assemble("
+                add [0], [0], [t]
+                mul [1], #300, [t]
+                add [t], [2], [t]
+                add [t], #7, [0]
+                halt
+            t:  data 0
+        ").unwrap();
+        let mut sym = Symbolic::new(&prog);
+        let (noun, verb) = (sym.symbol(), sym.symbol());
+        sym.set(1, noun);
+        sym.set(2, verb);
+        let e = sym.run().unwrap();
+        assert_eq!(e.linear(), Some(Linear {constant: 7, terms: vec![(0, 300), (1, 1)].into_iter().collect()}));
+
+        let sources = [Source::Cell(1), Source::Cell(2)];
+        let ranges = [0 ..= 99, 0 ..= 99];
+        for &target in [7, 4513, 15099, 29806, 30000, 29807].iter() {
+            let found = find(&prog, &sources, &ranges, Goal::Memory(0), target);
+            assert_eq!(found, brute(&prog, &sources, &ranges, Goal::Memory(0), target), "{}", target);
+        }
+        assert_eq!(find(&prog, &sources, &ranges, Goal::Memory(0), 4513), Some(vec![15, 6]));
+    }
+
+    // a * b + (a == b) + (a < 3), branch free but not linear
+    #[test]
+    fn nonlinear() {
+        let prog = assemble("
+                input [a]
+                input [b]
+                mul [a], [b], [t]
+                eq [a], [b], [u]
+                add [t], [u], [t]
+                le [a], #3, [u]
+                add [t], [u], [t]
+                output [t]
+                halt
+            a:  data 0
+            b:  data 0
+            t:  data 0
+            u:  data 0
+        ").unwrap();
+        let sources = [Source::Input, Source::Input];
+        let ranges = [-5 ..= 5, 0 ..= 9];
+        for target in -10 .. 40 {
+            let found = find(&prog, &sources, &ranges, Goal::Output(0), target);
+            assert_eq!(found, brute(&prog, &sources, &ranges, Goal::Output(0), target), "{}", target);
+        }
+        assert_eq!(find(&prog, &sources, &ranges, Goal::Output(0), 5), Some(vec![1, 4]));
+    }
+
+    #[test]
+    fn branches() {
+        let prog = assemble("
+                input [a]
+                jz [a], #zero
+                output [a]
+                halt
+            zero:
+                output #42
+                halt
+            a:  data 0
+        ").unwrap();
+        let mut sym = Symbolic::new(&prog);
+        let a = sym.symbol();
+        sym.feed(a);
+        assert_eq!(sym.run(), Err(Stuck::SymbolicBranch(2)));
+
+        let ranges = [0 ..= 50];
+        assert_eq!(find(&prog, &[Source::Input], &ranges, Goal::Output(0), 42), Some(vec![0]));
+        assert_eq!(find(&prog, &[Source::Input], &ranges, Goal::Output(0), 7), Some(vec![7]));
+        assert_eq!(find(&prog, &[Source::Input], &ranges, Goal::Output(0), 51), None);
+    }
+}
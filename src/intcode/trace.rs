@@ -0,0 +1,215 @@
+use super::{Opcode, Word};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+
+// what one executed instruction did; operands are the values read, in
+// parameter order, after resolving their modes
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record<W: Word = i64> {
+    pub pc: usize,
+    pub ins: W,
+    pub rb: W,
+    pub opcode: Option<Opcode<W>>,
+    pub operands: Vec<W>,
+    pub writes: Vec<(usize, W)>,
+    pub input: Option<W>,
+    pub output: Option<W>,
+    pub error: Option<String>
+}
+
+impl<W: Word> Record<W> {
+
+    pub fn new(pc: usize, ins: W, rb: W) -> Record<W> {
+        Record {
+            pc,
+            ins,
+            rb,
+            opcode: None,
+            operands: Vec::new(),
+            writes: Vec::new(),
+            input: None,
+            output: None,
+            error: None
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.opcode.as_ref().map(|op| op.mnemonic()).unwrap_or("?")
+    }
+
+    pub fn to_json(&self) -> String {
+        let operands: Vec<String> = self.operands.iter().map(|w| w.to_string()).collect();
+        let writes: Vec<String> = self.writes.iter().map(|(addr, w)| format!("[{},{}]", addr, w)).collect();
+        let mut json = format!("{{\"pc\":{},\"ins\":{},\"rb\":{},\"op\":\"{}\",\"operands\":[{}],\"writes\":[{}]",
+            self.pc, self.ins, self.rb, self.mnemonic(), operands.join(","), writes.join(","));
+        if let Some(val) = &self.input {
+            json += &format!(",\"input\":{}", val);
+        }
+        if let Some(val) = &self.output {
+            json += &format!(",\"output\":{}", val);
+        }
+        if let Some(error) = &self.error {
+            json += &format!(",\"error\":{}", json_string(error));
+        }
+        json + "}"
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c)
+        }
+    }
+    out + "\""
+}
+
+impl<W: Word> fmt::Display for Record<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operands: Vec<String> = self.operands.iter().map(|w| w.to_string()).collect();
+        let ins = format!("{:04}: {:<6} {}", self.pc, self.mnemonic(), operands.join(", "));
+        f.write_str(ins.trim_end())?;
+        for (addr, val) in self.writes.iter() {
+            write!(f, "  [{}] <- {}", addr, val)?;
+        }
+        if let Some(val) = &self.input {
+            write!(f, "  in {}", val)?;
+        }
+        if let Some(val) = &self.output {
+            write!(f, "  out {}", val)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, "  error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait TraceSink<W: Word> {
+    fn record(&mut self, rec: &Record<W>) -> io::Result<()>;
+}
+
+// one line per instruction, for reading
+pub struct TextTrace<O: Write>(pub O);
+
+// one JSON object per line, for tools
+pub struct JsonTrace<O: Write>(pub O);
+
+impl<W: Word, O: Write> TraceSink<W> for TextTrace<O> {
+    fn record(&mut self, rec: &Record<W>) -> io::Result<()> {
+        writeln!(self.0, "{}", rec)
+    }
+}
+
+impl<W: Word, O: Write> TraceSink<W> for JsonTrace<O> {
+    fn record(&mut self, rec: &Record<W>) -> io::Result<()> {
+        writeln!(self.0, "{}", rec.to_json())
+    }
+}
+
+// keeps only the last `cap` records, cheap enough to leave on for long runs
+pub struct RingTrace<W: Word = i64> {
+    cap: usize,
+    records: VecDeque<Record<W>>
+}
+
+impl<W: Word> RingTrace<W> {
+
+    pub fn new(cap: usize) -> RingTrace<W> {
+        RingTrace {cap, records: VecDeque::with_capacity(cap)}
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &Record<W>> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn dump(&self, sink: &mut dyn TraceSink<W>) -> io::Result<()> {
+        self.records.iter().try_for_each(|rec| sink.record(rec))
+    }
+}
+
+impl<W: Word> TraceSink<W> for RingTrace<W> {
+    fn record(&mut self, rec: &Record<W>) -> io::Result<()> {
+        if self.cap == 0 {
+            return Ok(());
+        }
+        if self.records.len() == self.cap {
+            self.records.pop_front();
+        }
+        self.records.push_back(rec.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::intcode::IntCodePC;
+
+    #[test]
+    fn records() {
+        let mut i = std::collections::VecDeque::from(vec![6]);
+        let mut o = Vec::new();
+        let mut ring = RingTrace::new(16);
+        let mut pc = IntCodePC::new_with_io(vec![3,9,1002,9,7,9,4,9,99,0], &mut i, &mut o);
+        pc.trace_to(&mut ring);
+        pc.run().unwrap();
+        drop(pc);
+
+        let recs: Vec<&Record> = ring.records().collect();
+        assert_eq!(recs.len(), 4);
+        assert_eq!(recs[0].input, Some(6));
+        assert_eq!(recs[0].writes, vec![(9, 6)]);
+        assert_eq!(recs[1].opcode, Some(Opcode::Mul));
+        assert_eq!(recs[1].operands, vec![6, 7]);
+        assert_eq!(recs[1].writes, vec![(9, 42)]);
+        assert_eq!(recs[2].output, Some(42));
+        assert_eq!(recs[3].opcode, Some(Opcode::Halt(3)));
+
+        assert_eq!(recs[1].to_string(), "0002: mul    6, 7  [9] <- 42");
+        assert_eq!(recs[2].to_json(), "{\"pc\":6,\"ins\":4,\"rb\":0,\"op\":\"output\",\"operands\":[42],\"writes\":[],\"output\":42}");
+    }
+
+    #[test]
+    fn ring() {
+        let mut ring = RingTrace::new(2);
+        let mut pc = IntCodePC::new_detached(vec![1101,1,1,0,1101,2,2,0,99]);
+        pc.trace_to(&mut ring);
+        pc.run().unwrap();
+        drop(pc);
+        assert_eq!(ring.records().map(|r| r.pc).collect::<Vec<_>>(), vec![4, 8]);
+
+        let mut text = TextTrace(Vec::new());
+        ring.dump(&mut text).unwrap();
+        assert_eq!(String::from_utf8(text.0).unwrap(), "0004: add    2, 2  [0] <- 4\n0008: halt\n");
+    }
+
+    #[test]
+    fn errors() {
+        let mut json = JsonTrace(Vec::new());
+        let mut pc = IntCodePC::new_detached(vec![1101,1,1,0,3,0]);
+        pc.trace_to(&mut json);
+        assert!(pc.run().is_err());
+        drop(pc);
+
+        let lines = String::from_utf8(json.0).unwrap();
+        let last = lines.lines().last().unwrap();
+        assert_eq!(last, "{\"pc\":4,\"ins\":3,\"rb\":0,\"op\":\"input\",\"operands\":[],\"writes\":[],\"error\":\"input exhausted in 3 at 4\"}");
+        assert_eq!(json_string("a\"b\n"), "\"a\\\"b\\n\"");
+    }
+}
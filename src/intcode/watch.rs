@@ -0,0 +1,89 @@
+use super::Word;
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write
+}
+
+// one watched access; for reads old and new are both the value read
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hit<W: Word = i64> {
+    pub pc: usize,
+    pub addr: usize,
+    pub access: Access,
+    pub old: W,
+    pub new: W
+}
+
+impl<W: Word> fmt::Display for Hit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.access {
+            Access::Read => write!(f, "[{}] read at {}: {}", self.addr, self.pc, self.new),
+            Access::Write => write!(f, "[{}] written at {}: {} -> {}", self.addr, self.pc, self.old, self.new)
+        }
+    }
+}
+
+// the addresses IntCodePC reports accesses to, and the hits not yet taken
+#[derive(Debug)]
+pub struct Watches<W: Word = i64> {
+    reads: BTreeSet<usize>,
+    writes: BTreeSet<usize>,
+    hits: Vec<Hit<W>>
+}
+
+impl<W: Word> Watches<W> {
+
+    pub fn new() -> Watches<W> {
+        Watches {
+            reads: BTreeSet::new(),
+            writes: BTreeSet::new(),
+            hits: Vec::new()
+        }
+    }
+
+    pub fn watch(&mut self, addr: usize, access: Access) {
+        match access {
+            Access::Read => self.reads.insert(addr),
+            Access::Write => self.writes.insert(addr)
+        };
+    }
+
+    pub fn unwatch(&mut self, addr: usize, access: Access) -> bool {
+        match access {
+            Access::Read => self.reads.remove(&addr),
+            Access::Write => self.writes.remove(&addr)
+        }
+    }
+
+    pub fn is_watched(&self, addr: usize, access: Access) -> bool {
+        match access {
+            Access::Read => self.reads.contains(&addr),
+            Access::Write => self.writes.contains(&addr)
+        }
+    }
+
+    pub fn watched(&self, access: Access) -> impl Iterator<Item = usize> + '_ {
+        match access {
+            Access::Read => self.reads.iter().cloned(),
+            Access::Write => self.writes.iter().cloned()
+        }
+    }
+
+    pub fn record(&mut self, hit: Hit<W>) {
+        self.hits.push(hit);
+    }
+
+    pub fn take_hits(&mut self) -> Vec<Hit<W>> {
+        std::mem::take(&mut self.hits)
+    }
+}
+
+impl<W: Word> Default for Watches<W> {
+    fn default() -> Self {
+        Watches::new()
+    }
+}
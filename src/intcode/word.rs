@@ -0,0 +1,120 @@
+use std::fmt::{Debug, Display};
+
+pub trait Word: Clone + PartialEq + PartialOrd + Debug + Display + 'static {
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    fn from_i64(val: i64) -> Self;
+
+    fn to_i64(&self) -> Option<i64>;
+
+    fn parse(s: &str) -> Result<Self, String>;
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+
+    fn to_address(&self) -> Option<usize> {
+        match self.to_i64() {
+            Some(val) if val >= 0 => Some(val as usize),
+            _ => None
+        }
+    }
+}
+
+impl Word for i64 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn from_i64(val: i64) -> Self {
+        val
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn parse(s: &str) -> Result<Self, String> {
+        s.parse::<i64>().map_err(|e| e.to_string())
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        i64::checked_add(*self, *rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *rhs)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl Word for num_bigint::BigInt {
+    fn zero() -> Self {
+        num_bigint::BigInt::from(0)
+    }
+
+    fn one() -> Self {
+        num_bigint::BigInt::from(1)
+    }
+
+    fn from_i64(val: i64) -> Self {
+        num_bigint::BigInt::from(val)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        use std::convert::TryFrom;
+        i64::try_from(self).ok()
+    }
+
+    fn parse(s: &str) -> Result<Self, String> {
+        s.parse::<num_bigint::BigInt>().map_err(|e| e.to_string())
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn overflow() {
+        assert_eq!(Word::checked_add(&i64::MAX, &0), Some(i64::MAX));
+        assert_eq!(Word::checked_add(&i64::MAX, &1), None);
+        assert_eq!(Word::checked_mul(&i64::MIN, &-1), None);
+    }
+
+    #[test]
+    fn address() {
+        assert_eq!(42i64.to_address(), Some(42));
+        assert_eq!((-1i64).to_address(), None);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint() {
+        use num_bigint::BigInt;
+
+        let max = BigInt::from_i64(i64::MAX);
+        let sum = Word::checked_add(&max, &BigInt::one()).unwrap();
+        assert_eq!(sum.to_i64(), None);
+        assert_eq!(sum, <BigInt as Word>::parse("9223372036854775808").unwrap());
+    }
+}
@@ -1,5 +1,5 @@
 pub mod intcode;
-mod graph;
+pub mod graph;
 
 pub mod day;
 pub mod day1;
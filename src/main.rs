@@ -21,7 +21,7 @@ fn main() {
 
     match args().nth(1) {
         Some(day_arg) => {
-            if let Some(day) = day_arg.parse::<usize>().ok() {
+            if let Ok(day) = day_arg.parse::<usize>() {
                 match day {
                     1 => {
                         let d = Day1{};
@@ -0,0 +1,83 @@
+// Builds the Rust source `intcodepc compile` generated for the programs in
+// tests/compiled and checks it runs them the same as the interpreter. After a
+// change to the translation the files are rebuilt with
+//
+//     intcodepc compile tests/compiled/<name>.txt <name> > tests/compiled/<name>.rs
+
+use aoc2019::intcode::compile::to_rust;
+use aoc2019::intcode::{Access, IntCodePC, Limits, Result};
+use std::collections::VecDeque;
+use std::fs;
+
+mod generated
+{
+    include!("compiled/cmp8.rs");
+    include!("compiled/quine.rs");
+    include!("compiled/rewrite.rs");
+}
+
+type Run = fn(&mut IntCodePC<'_, '_>) -> Result<i64>;
+
+const PROGRAMS: [(&str, Run); 3] = [
+    ("cmp8", generated::cmp8),
+    ("quine", generated::quine),
+    ("rewrite", generated::rewrite)
+];
+
+fn program(name: &str) -> Vec<i64> {
+    let text = fs::read_to_string(format!("tests/compiled/{}.txt", name)).unwrap();
+    text.split(',').map(|t| t.trim().parse().unwrap()).collect()
+}
+
+// runs `prog` on `input` with `setup` applied to the machine, returning the
+// result, output and memory
+fn outcome(prog: &[i64], input: &[i64], run: Option<Run>, setup: &dyn Fn(&mut IntCodePC<'_, '_>)) -> String {
+    let mut i: VecDeque<i64> = input.iter().cloned().collect();
+    let mut o: Vec<i64> = Vec::new();
+    let mut pc = IntCodePC::new_with_io(prog.to_vec(), &mut i, &mut o);
+    setup(&mut pc);
+    let res = match run {
+        Some(run) => run(&mut pc),
+        None => pc.run()
+    };
+    let memory = pc.memory().to_vec();
+    let hits = pc.take_hits();
+    drop(pc);
+    format!("{:?} {:?} {:?} {:?}", res, o, memory, hits)
+}
+
+#[test]
+fn up_to_date() {
+    for (name, _) in PROGRAMS.iter() {
+        let src = fs::read_to_string(format!("tests/compiled/{}.rs", name)).unwrap();
+        assert!(to_rust(&program(name), name, &[]) == src, "tests/compiled/{}.rs is out of date", name);
+    }
+}
+
+#[test]
+fn same_as_interpreter() {
+    for &(name, run) in PROGRAMS.iter() {
+        let prog = program(name);
+        for val in 7 ..= 9 {
+            assert_eq!(outcome(&prog, &[val], Some(run), &|_| {}), outcome(&prog, &[val], None, &|_| {}), "{}", name);
+        }
+    }
+}
+
+// a patched image, limits and watchpoints all leave the run to the
+// interpreter, so they behave the same either way
+#[test]
+fn hooks() {
+    let setups: [&dyn Fn(&mut IntCodePC<'_, '_>); 4] = [
+        &|pc| pc.memory_mut().set(1, 20),
+        &|pc| pc.set_limits(Limits {max_steps: Some(5), ..Default::default()}),
+        &|pc| pc.watch(20, Access::Write),
+        &|pc| pc.watch(100, Access::Read)
+    ];
+    for &(name, run) in PROGRAMS.iter() {
+        let prog = program(name);
+        for setup in setups.iter() {
+            assert_eq!(outcome(&prog, &[8], Some(run), setup), outcome(&prog, &[8], None, setup), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+// Generated by intcodepc compile. Runs an Intcode program on `vm`, whose
+// memory has to start out as the image it was compiled from except for
+// the words []; whatever the translation does not cover,
+// including instructions the program overwrote and machines with hooks,
+// is left to the interpreter.
+#[allow(dead_code, unused_labels, unused_mut, unused_variables, clippy::all)]
+pub fn cmp8(vm: &mut aoc2019::intcode::IntCodePC<'_, '_>) -> aoc2019::intcode::Result<i64> {
+    const BAKED: [(usize, i64); 44] = [(0, 3), (1, 21), (2, 1008), (3, 21), (4, 8), (5, 20), (6, 1005), (7, 20), (8, 22), (9, 107), (10, 8), (11, 21), (12, 20), (13, 1006), (14, 20), (15, 31), (16, 1106), (17, 0), (18, 36), (22, 1002), (23, 21), (24, 125), (25, 20), (26, 4), (27, 20), (28, 1105), (29, 1), (30, 46), (31, 104), (32, 999), (33, 1105), (34, 1), (35, 46), (36, 1101), (37, 1000), (38, 1), (39, 20), (40, 4), (41, 20), (42, 1105), (43, 1), (44, 46), (45, 98), (46, 99)];
+    const OWNERS: [&[usize]; 47] = [&[0], &[0], &[2], &[2], &[2, 4], &[2, 4], &[4, 6], &[4, 6], &[6], &[9], &[9, 10], &[9, 10], &[9, 10], &[10, 13], &[13], &[13], &[16], &[16], &[16], &[], &[], &[], &[22], &[22], &[22], &[22], &[26], &[26], &[28], &[28, 29], &[28, 29], &[29, 31], &[29, 31], &[33], &[33, 34], &[33, 34], &[34, 36], &[34, 36], &[36, 38], &[36, 38], &[38, 40], &[38, 40], &[42], &[42, 43], &[42, 43], &[43], &[43]];
+
+    fn addr(val: i64) -> Option<usize> {
+        if val >= 0 { Some(val as usize) } else { None }
+    }
+
+    fn rel(rb: i64, off: i64) -> Option<usize> {
+        rb.checked_add(off).and_then(addr)
+    }
+
+    fn mark(stale: &mut [bool; 47], pos: usize) {
+        if let Some(owners) = OWNERS.get(pos) {
+            for &start in owners.iter() {
+                stale[start] = true;
+            }
+        }
+    }
+
+    if vm.has_hooks() {
+        return vm.run();
+    }
+
+    let mut stale = [false; 47];
+    for &(pos, word) in BAKED.iter() {
+        if vm.memory().get(pos) != word {
+            mark(&mut stale, pos);
+        }
+    }
+
+    let mut pc = vm.pc();
+    let mut rb = *vm.rb();
+    'run: loop {
+        match pc {
+            0 if !stale[0] => 'ins: {
+                let m = vm.memory();
+                let Some(pos) = addr(21i64) else { break 'ins };
+                vm.set_registers(pc, rb);
+                vm.step()?;
+                mark(&mut stale, pos);
+                pc = vm.pc();
+                continue 'run;
+            },
+            2 if !stale[2] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(21i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(8i64) else { break 'ins };
+                let val = (a == b) as i64;
+                let Some(pos) = addr(20i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 6;
+                continue 'run;
+            },
+            4 if !stale[4] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(20i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(1005i64).map(|p| m.get(p)) else { break 'ins };
+                let val = (a == b) as i64;
+                let Some(pos) = addr(20i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 8;
+                continue 'run;
+            },
+            6 if !stale[6] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(20i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(22i64) else { break 'ins };
+                if a != 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 9;
+                }
+                continue 'run;
+            },
+            9 if !stale[9] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(8i64) else { break 'ins };
+                let Some(b) = addr(21i64).map(|p| m.get(p)) else { break 'ins };
+                let val = (a < b) as i64;
+                let Some(pos) = addr(20i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 13;
+                continue 'run;
+            },
+            10 if !stale[10] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(21i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(20i64).map(|p| m.get(p)) else { break 'ins };
+                let val = (a == b) as i64;
+                let Some(pos) = addr(1006i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 14;
+                continue 'run;
+            },
+            13 if !stale[13] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(20i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(31i64) else { break 'ins };
+                if a == 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 16;
+                }
+                continue 'run;
+            },
+            16 if !stale[16] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(0i64) else { break 'ins };
+                let Some(b) = Some(36i64) else { break 'ins };
+                if a == 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 19;
+                }
+                continue 'run;
+            },
+            22 if !stale[22] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(21i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(125i64) else { break 'ins };
+                let Some(val) = a.checked_mul(b) else { break 'ins };
+                let Some(pos) = addr(20i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 26;
+                continue 'run;
+            },
+            26 if !stale[26] => 'ins: {
+                vm.set_registers(pc, rb);
+                vm.step()?;
+                pc = vm.pc();
+                continue 'run;
+            },
+            28 if !stale[28] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(1i64) else { break 'ins };
+                let Some(b) = Some(46i64) else { break 'ins };
+                if a != 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 31;
+                }
+                continue 'run;
+            },
+            29 if !stale[29] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(46i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(104i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(999i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 33;
+                continue 'run;
+            },
+            31 if !stale[31] => 'ins: {
+                vm.set_registers(pc, rb);
+                vm.step()?;
+                pc = vm.pc();
+                continue 'run;
+            },
+            33 if !stale[33] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(1i64) else { break 'ins };
+                let Some(b) = Some(46i64) else { break 'ins };
+                if a != 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 36;
+                }
+                continue 'run;
+            },
+            34 if !stale[34] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(46i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(1101i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(1000i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 38;
+                continue 'run;
+            },
+            36 if !stale[36] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(1000i64) else { break 'ins };
+                let Some(b) = Some(1i64) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(20i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 40;
+                continue 'run;
+            },
+            38 if !stale[38] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(20i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(4i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(20i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 42;
+                continue 'run;
+            },
+            40 if !stale[40] => 'ins: {
+                vm.set_registers(pc, rb);
+                vm.step()?;
+                pc = vm.pc();
+                continue 'run;
+            },
+            42 if !stale[42] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(1i64) else { break 'ins };
+                let Some(b) = Some(46i64) else { break 'ins };
+                if a != 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 45;
+                }
+                continue 'run;
+            },
+            43 if !stale[43] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(46i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(98i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(99i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 47;
+                continue 'run;
+            },
+            _ => {}
+        }
+        vm.set_registers(pc, rb);
+        return vm.run();
+    }
+}
@@ -0,0 +1,128 @@
+// Generated by intcodepc compile. Runs an Intcode program on `vm`, whose
+// memory has to start out as the image it was compiled from except for
+// the words []; whatever the translation does not cover,
+// including instructions the program overwrote and machines with hooks,
+// is left to the interpreter.
+#[allow(dead_code, unused_labels, unused_mut, unused_variables, clippy::all)]
+pub fn quine(vm: &mut aoc2019::intcode::IntCodePC<'_, '_>) -> aoc2019::intcode::Result<i64> {
+    const BAKED: [(usize, i64); 15] = [(0, 109), (1, 1), (2, 204), (3, -1), (4, 1001), (5, 100), (6, 1), (7, 100), (8, 1008), (9, 100), (10, 16), (11, 101), (12, 1006), (13, 101), (14, 0)];
+    const OWNERS: [&[usize]; 16] = [&[0], &[0, 1], &[1, 2], &[1, 2], &[1, 4], &[4], &[4, 6], &[4, 6], &[6, 8], &[6, 8], &[8], &[8, 11], &[11, 12], &[11, 12], &[11, 12], &[]];
+
+    fn addr(val: i64) -> Option<usize> {
+        if val >= 0 { Some(val as usize) } else { None }
+    }
+
+    fn rel(rb: i64, off: i64) -> Option<usize> {
+        rb.checked_add(off).and_then(addr)
+    }
+
+    fn mark(stale: &mut [bool; 16], pos: usize) {
+        if let Some(owners) = OWNERS.get(pos) {
+            for &start in owners.iter() {
+                stale[start] = true;
+            }
+        }
+    }
+
+    if vm.has_hooks() {
+        return vm.run();
+    }
+
+    let mut stale = [false; 16];
+    for &(pos, word) in BAKED.iter() {
+        if vm.memory().get(pos) != word {
+            mark(&mut stale, pos);
+        }
+    }
+
+    let mut pc = vm.pc();
+    let mut rb = *vm.rb();
+    'run: loop {
+        match pc {
+            0 if !stale[0] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(1i64) else { break 'ins };
+                let Some(base) = rb.checked_add(a) else { break 'ins };
+                rb = base;
+                pc = 2;
+                continue 'run;
+            },
+            1 if !stale[1] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(204i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(-1i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(1001i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 5;
+                continue 'run;
+            },
+            2 if !stale[2] => 'ins: {
+                vm.set_registers(pc, rb);
+                vm.step()?;
+                pc = vm.pc();
+                continue 'run;
+            },
+            4 if !stale[4] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(100i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(1i64) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(100i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 8;
+                continue 'run;
+            },
+            6 if !stale[6] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(100i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(1008i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(100i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 10;
+                continue 'run;
+            },
+            8 if !stale[8] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(100i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(16i64) else { break 'ins };
+                let val = (a == b) as i64;
+                let Some(pos) = addr(101i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 12;
+                continue 'run;
+            },
+            11 if !stale[11] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(1006i64) else { break 'ins };
+                let Some(b) = addr(101i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(0i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 15;
+                continue 'run;
+            },
+            12 if !stale[12] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(101i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(0i64) else { break 'ins };
+                if a == 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 15;
+                }
+                continue 'run;
+            },
+            _ => {}
+        }
+        vm.set_registers(pc, rb);
+        return vm.run();
+    }
+}
@@ -0,0 +1,115 @@
+// Generated by intcodepc compile. Runs an Intcode program on `vm`, whose
+// memory has to start out as the image it was compiled from except for
+// the words []; whatever the translation does not cover,
+// including instructions the program overwrote and machines with hooks,
+// is left to the interpreter.
+#[allow(dead_code, unused_labels, unused_mut, unused_variables, clippy::all)]
+pub fn rewrite(vm: &mut aoc2019::intcode::IntCodePC<'_, '_>) -> aoc2019::intcode::Result<i64> {
+    const BAKED: [(usize, i64); 18] = [(0, 1), (1, 20), (2, 21), (3, 22), (4, 1001), (5, 23), (6, -1), (7, 23), (8, 1101), (9, 0), (10, 2), (11, 0), (12, 1005), (13, 23), (14, 0), (20, 6), (21, 7), (22, 0)];
+    const OWNERS: [&[usize]; 24] = [&[0], &[0], &[0], &[0], &[4], &[4], &[4], &[4], &[8], &[8], &[8, 10], &[8, 10], &[10, 12], &[10, 12], &[12], &[], &[], &[], &[], &[], &[20], &[20], &[20], &[]];
+
+    fn addr(val: i64) -> Option<usize> {
+        if val >= 0 { Some(val as usize) } else { None }
+    }
+
+    fn rel(rb: i64, off: i64) -> Option<usize> {
+        rb.checked_add(off).and_then(addr)
+    }
+
+    fn mark(stale: &mut [bool; 24], pos: usize) {
+        if let Some(owners) = OWNERS.get(pos) {
+            for &start in owners.iter() {
+                stale[start] = true;
+            }
+        }
+    }
+
+    if vm.has_hooks() {
+        return vm.run();
+    }
+
+    let mut stale = [false; 24];
+    for &(pos, word) in BAKED.iter() {
+        if vm.memory().get(pos) != word {
+            mark(&mut stale, pos);
+        }
+    }
+
+    let mut pc = vm.pc();
+    let mut rb = *vm.rb();
+    'run: loop {
+        match pc {
+            0 if !stale[0] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(20i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(21i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(22i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 4;
+                continue 'run;
+            },
+            4 if !stale[4] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(23i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(-1i64) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(23i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 8;
+                continue 'run;
+            },
+            8 if !stale[8] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = Some(0i64) else { break 'ins };
+                let Some(b) = Some(2i64) else { break 'ins };
+                let Some(val) = a.checked_add(b) else { break 'ins };
+                let Some(pos) = addr(0i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 12;
+                continue 'run;
+            },
+            10 if !stale[10] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(0i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(1005i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(val) = a.checked_mul(b) else { break 'ins };
+                let Some(pos) = addr(23i64) else { break 'ins };
+                vm.store_word(pos, val);
+                mark(&mut stale, pos);
+                pc = 14;
+                continue 'run;
+            },
+            12 if !stale[12] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(23i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = Some(0i64) else { break 'ins };
+                if a != 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 15;
+                }
+                continue 'run;
+            },
+            20 if !stale[20] => 'ins: {
+                let m = vm.memory();
+                let Some(a) = addr(7i64).map(|p| m.get(p)) else { break 'ins };
+                let Some(b) = addr(0i64).map(|p| m.get(p)) else { break 'ins };
+                if a == 0 {
+                    let Some(to) = addr(b) else { break 'ins };
+                    pc = to;
+                } else {
+                    pc = 23;
+                }
+                continue 'run;
+            },
+            _ => {}
+        }
+        vm.set_registers(pc, rb);
+        return vm.run();
+    }
+}